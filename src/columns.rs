@@ -0,0 +1,69 @@
+use tf_demo_parser::demo::message::packetentities::EntityId;
+
+/// Give every building a fixed column for as long as it exists.
+///
+/// The building map is ordered by entity id, so the position of a building in it shifts whenever
+/// a building before it gets removed. Instead, a building gets a column the first time it's seen
+/// and keeps it until it's removed. A removed building's column is only handed out again on a
+/// later update, so there is always at least one update with an empty column between two
+/// buildings.
+#[derive(Debug, Default)]
+pub struct BuildingColumns {
+    columns: Vec<Option<EntityId>>,
+}
+
+impl BuildingColumns {
+    pub fn update(&mut self, buildings: &[EntityId]) {
+        for id in buildings {
+            if self.column(*id).is_some() {
+                continue;
+            }
+            match self.columns.iter_mut().find(|column| column.is_none()) {
+                Some(free) => *free = Some(*id),
+                None => self.columns.push(Some(*id)),
+            }
+        }
+
+        for column in self.columns.iter_mut() {
+            if column.is_some_and(|id| !buildings.contains(&id)) {
+                *column = None;
+            }
+        }
+    }
+
+    /// Get the column for a building
+    pub fn column(&self, id: EntityId) -> Option<usize> {
+        self.columns.iter().position(|column| *column == Some(id))
+    }
+
+    /// Get the building in a column, `None` if the column is empty
+    pub fn building(&self, column: usize) -> Option<EntityId> {
+        self.columns.get(column).copied().flatten()
+    }
+
+    /// The number of columns that have been used so far
+    pub fn column_count(&self) -> usize {
+        self.columns.len()
+    }
+}
+
+#[test]
+fn test_building_columns() {
+    let id = |id: u32| EntityId::from(id);
+    let mut columns = BuildingColumns::default();
+    columns.update(&[id(10), id(20), id(30)]);
+    assert_eq!(Some(1), columns.column(id(20)));
+
+    // removing a building doesn't move the ones after it
+    columns.update(&[id(20), id(30), id(40)]);
+    assert_eq!(None, columns.column(id(10)));
+    assert_eq!(Some(1), columns.column(id(20)));
+    assert_eq!(Some(2), columns.column(id(30)));
+    assert_eq!(Some(3), columns.column(id(40)));
+    assert_eq!(None, columns.building(0));
+
+    // the free column gets reused on the next update
+    columns.update(&[id(20), id(30), id(40), id(50)]);
+    assert_eq!(Some(0), columns.column(id(50)));
+    assert_eq!(4, columns.column_count());
+}
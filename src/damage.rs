@@ -0,0 +1,88 @@
+use crate::kill::CritType;
+use tf_demo_parser::demo::data::DemoTick;
+use tf_demo_parser::demo::gamevent::{ObjectDeflectedEvent, PlayerHealedEvent, PlayerHurtEvent};
+use tf_demo_parser::demo::parser::gamestateanalyser::Team;
+
+#[derive(Debug, Clone)]
+pub struct DamageRecord {
+    pub tick: DemoTick,
+    pub attacker_id: u16,
+    pub victim_id: u16,
+    pub damage: u16,
+    /// Health of the victim after taking the damage
+    pub victim_health: u16,
+    pub weapon_id: u16,
+    pub crit_type: CritType,
+}
+
+impl DamageRecord {
+    pub fn new(tick: DemoTick, hurt: &PlayerHurtEvent) -> Self {
+        let crit_type = if hurt.crit {
+            CritType::Crit
+        } else if hurt.mini_crit {
+            CritType::MiniCrit
+        } else {
+            CritType::None
+        };
+        DamageRecord {
+            tick,
+            attacker_id: hurt.attacker,
+            victim_id: hurt.user_id,
+            damage: hurt.damage_amount,
+            victim_health: hurt.health,
+            weapon_id: hurt.weapon_id,
+            crit_type,
+        }
+    }
+
+    /// Rocket and sticky jumps also count as damage
+    pub fn is_self_damage(&self) -> bool {
+        self.attacker_id == self.victim_id
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HealRecord {
+    pub tick: DemoTick,
+    pub healer_id: u16,
+    pub patient_id: u16,
+    pub amount: u16,
+}
+
+impl HealRecord {
+    pub fn new(tick: DemoTick, healed: &PlayerHealedEvent) -> Self {
+        HealRecord {
+            tick,
+            healer_id: healed.healer,
+            patient_id: healed.patient,
+            amount: healed.amount,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeflectRecord {
+    pub tick: DemoTick,
+    /// The pyro that reflected the projectile
+    pub deflector_id: u16,
+    /// The player that fired the projectile
+    pub owner_id: u16,
+    /// 0 when a player got pushed instead of a projectile
+    pub weapon_id: u16,
+    pub projectile_entity: u16,
+    /// The team the projectile belongs to after being reflected
+    pub team: Team,
+}
+
+impl DeflectRecord {
+    pub fn new(tick: DemoTick, deflected: &ObjectDeflectedEvent, team: Team) -> Self {
+        DeflectRecord {
+            tick,
+            deflector_id: deflected.user_id,
+            owner_id: deflected.owner_id,
+            weapon_id: deflected.weapon_id,
+            projectile_entity: deflected.object_entindex,
+            team,
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use crate::kill::KillRecord;
+use crate::state::PlayerState;
+use crate::{FlatState, WorldBoundaries};
+use tf_demo_parser::demo::parser::gamestateanalyser::World;
+use wasm_bindgen::prelude::*;
+
+/// Filter for the kills or player positions included in a heatmap
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy)]
+pub struct HeatmapFilter {
+    /// Only include kills made by (or deaths of) players from this team
+    pub team: Option<u8>,
+    /// Only include kills made by (or deaths of) players of this class
+    pub class: Option<u8>,
+    /// Only include kills made by (or deaths of) this player slot
+    pub player: Option<usize>,
+    pub start_tick: u32,
+    /// End of the tick range, exclusive
+    pub end_tick: u32,
+    /// Bin the victim position for deaths instead of the attacker position for kills,
+    /// ignored for occupancy heatmaps
+    pub deaths: bool,
+}
+
+impl Default for HeatmapFilter {
+    fn default() -> Self {
+        HeatmapFilter {
+            team: None,
+            class: None,
+            player: None,
+            start_tick: 0,
+            end_tick: u32::MAX,
+            deaths: false,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl HeatmapFilter {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl HeatmapFilter {
+    fn matches(&self, kill: &KillRecord) -> bool {
+        let (slot, team, class) = if self.deaths {
+            (kill.victim_slot, kill.victim_team, kill.victim_class)
+        } else {
+            (kill.attacker_slot, kill.attacker_team, kill.attacker_class)
+        };
+        let tick = u32::from(kill.tick);
+
+        tick >= self.start_tick
+            && tick < self.end_tick
+            && self.team.is_none_or(|filter| filter == team as u8)
+            && self.class.is_none_or(|filter| filter == class as u8)
+            && self.player.is_none_or(|filter| Some(filter) == slot)
+    }
+
+    fn matches_player(&self, slot: usize, player: &PlayerState) -> bool {
+        player.health > 0
+            && self.team.is_none_or(|filter| filter == player.team as u8)
+            && self.class.is_none_or(|filter| filter == player.class as u8)
+            && self.player.is_none_or(|filter| filter == slot)
+    }
+}
+
+/// A density grid over the world boundaries.
+///
+/// Cells are stored row by row, starting at the minimum x and y of the world.
+pub struct Grid {
+    boundaries: WorldBoundaries,
+    width: usize,
+    height: usize,
+    cells: Vec<f32>,
+}
+
+impl Grid {
+    pub fn new(boundaries: WorldBoundaries, width: usize, height: usize) -> Self {
+        Grid {
+            boundaries,
+            width,
+            height,
+            cells: vec![0.0; width * height],
+        }
+    }
+
+    /// Add `weight` to the cell containing the point, points outside the world are ignored
+    pub fn add(&mut self, x: f32, y: f32, weight: f32) {
+        let min = self.boundaries.boundary_min;
+        let max = self.boundaries.boundary_max;
+        let x_ratio = (x - min.x) / (max.x - min.x);
+        let y_ratio = (y - min.y) / (max.y - min.y);
+        // also filters out NaN
+        if self.cells.is_empty()
+            || !((0.0..=1.0).contains(&x_ratio) && (0.0..=1.0).contains(&y_ratio))
+        {
+            return;
+        }
+
+        let column = ((x_ratio * self.width as f32) as usize).min(self.width - 1);
+        let row = ((y_ratio * self.height as f32) as usize).min(self.height - 1);
+        self.cells[row * self.width + column] += weight;
+    }
+
+    pub fn into_cells(self) -> Box<[f32]> {
+        self.cells.into_boxed_slice()
+    }
+}
+
+/// Count the kills matching the filter in a `width` x `height` grid over the world boundaries
+pub fn kill_heatmap(
+    kills: &[KillRecord],
+    boundaries: WorldBoundaries,
+    filter: &HeatmapFilter,
+    width: usize,
+    height: usize,
+) -> Box<[f32]> {
+    let mut grid = Grid::new(boundaries, width, height);
+    for kill in kills.iter().filter(|kill| filter.matches(kill)) {
+        let position = if filter.deaths {
+            kill.victim_position
+        } else {
+            kill.attacker_position
+        };
+        if let Some(position) = position {
+            grid.add(position.x, position.y, 1.0);
+        }
+    }
+    grid.into_cells()
+}
+
+/// Sum the time in seconds that alive players matching the filter spent in each cell of a
+/// `width` x `height` grid over the world boundaries
+pub fn occupancy_heatmap(
+    state: &FlatState,
+    filter: &HeatmapFilter,
+    width: usize,
+    height: usize,
+) -> Box<[f32]> {
+    let world = World::from(state.boundaries);
+    let mut grid = Grid::new(state.boundaries, width, height);
+
+    let tick_count = state.tick_count as usize;
+    let start = (filter.start_tick as usize).min(tick_count);
+    let end = (filter.end_tick as usize).clamp(start, tick_count);
+    let column_size = tick_count * PlayerState::PACKET_SIZE;
+
+    for slot in 0..state.player_count {
+        if filter.player.is_some_and(|filter| filter != slot) {
+            continue;
+        }
+
+        let column = &state.data[slot * column_size..(slot + 1) * column_size];
+        let packets = &column[start * PlayerState::PACKET_SIZE..end * PlayerState::PACKET_SIZE];
+        for packet in packets.chunks_exact(PlayerState::PACKET_SIZE) {
+            let Ok(bytes) = packet.try_into() else {
+                continue;
+            };
+            let player = PlayerState::unpack(bytes, &world);
+            if filter.matches_player(slot, &player) {
+                grid.add(
+                    player.position.x,
+                    player.position.y,
+                    state.interval_per_tick,
+                );
+            }
+        }
+    }
+
+    grid.into_cells()
+}
+
+#[test]
+fn test_grid() {
+    use crate::XY;
+
+    let boundaries = WorldBoundaries {
+        boundary_min: XY {
+            x: -100.0,
+            y: -100.0,
+        },
+        boundary_max: XY { x: 100.0, y: 100.0 },
+    };
+    let mut grid = Grid::new(boundaries, 4, 2);
+    grid.add(-100.0, -100.0, 1.0);
+    grid.add(-60.0, -10.0, 1.0);
+    grid.add(100.0, 100.0, 2.0);
+    grid.add(101.0, 0.0, 1.0);
+    grid.add(f32::NAN, 0.0, 1.0);
+
+    assert_eq!(
+        vec![2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 2.0],
+        grid.into_cells().into_vec()
+    );
+}
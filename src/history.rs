@@ -0,0 +1,33 @@
+use tf_demo_parser::demo::data::DemoTick;
+
+/// An entry in a list of values that change over time, the list is sorted by tick
+pub trait Timed {
+    /// The tick the entry starts at
+    fn tick(&self) -> DemoTick;
+}
+
+/// Get the last entry at or before a tick
+pub fn entry_at<T: Timed>(history: &[T], tick: DemoTick) -> Option<&T> {
+    let index = history.partition_point(|entry| entry.tick() <= tick);
+    index.checked_sub(1).map(|index| &history[index])
+}
+
+#[test]
+fn test_entry_at() {
+    struct Entry(u32, u8);
+
+    impl Timed for Entry {
+        fn tick(&self) -> DemoTick {
+            DemoTick::from(self.0)
+        }
+    }
+
+    let history = [Entry(10, 1), Entry(20, 2), Entry(20, 3), Entry(30, 4)];
+    let value_at = |tick: u32| entry_at(&history, DemoTick::from(tick)).map(|entry| entry.1);
+    assert_eq!(None, value_at(5));
+    assert_eq!(Some(1), value_at(10));
+    assert_eq!(Some(1), value_at(19));
+    // the last entry wins when multiple entries share a tick
+    assert_eq!(Some(3), value_at(20));
+    assert_eq!(Some(4), value_at(100));
+}
@@ -0,0 +1,177 @@
+use crate::history::{entry_at, Timed};
+use serde::Serialize;
+use tf_demo_parser::demo::data::DemoTick;
+use tf_demo_parser::demo::parser::gamestateanalyser::UserId;
+
+/// A stretch of time during which a single user occupied a player slot
+#[derive(Debug, Clone, Serialize)]
+pub struct SlotOccupancy {
+    pub slot: usize,
+    pub user_id: UserId,
+    pub steam_id: String,
+    pub name: String,
+    pub connect_tick: DemoTick,
+    /// `None` if the user was still connected at the end of the demo
+    pub disconnect_tick: Option<DemoTick>,
+}
+
+/// Find the player slot a user occupied
+pub fn slot_for_user(occupancies: &[SlotOccupancy], user_id: UserId) -> Option<usize> {
+    occupancies
+        .iter()
+        .rev()
+        .find(|occupancy| occupancy.user_id == user_id)
+        .map(|occupancy| occupancy.slot)
+}
+
+/// Find the occupancy a user had at a tick
+///
+/// Events can arrive slightly before a user shows up in a player slot, in which case their first
+/// occupancy is used
+pub fn occupancy_at(
+    occupancies: &[SlotOccupancy],
+    user_id: UserId,
+    tick: DemoTick,
+) -> Option<usize> {
+    occupancies
+        .iter()
+        .rposition(|occupancy| occupancy.user_id == user_id && occupancy.connect_tick <= tick)
+        .or_else(|| {
+            occupancies
+                .iter()
+                .position(|occupancy| occupancy.user_id == user_id)
+        })
+}
+
+/// A name a player slot switched to at a specific tick
+#[derive(Debug, Clone, Serialize)]
+pub struct NameChange {
+    pub tick: DemoTick,
+    pub name: String,
+}
+
+impl Timed for NameChange {
+    fn tick(&self) -> DemoTick {
+        self.tick
+    }
+}
+
+/// Get the name a player had at a specific tick from its name history
+///
+/// Before the first recorded name change, the first known name is used
+pub fn name_at(history: &[NameChange], tick: DemoTick) -> Option<&str> {
+    entry_at(history, tick)
+        .or_else(|| history.first())
+        .map(|change| change.name.as_str())
+}
+
+/// A single logical player, merging all the slots they occupied by steam id
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerIdentity {
+    pub steam_id: String,
+    pub name: String,
+    pub occupancies: Vec<SlotOccupancy>,
+}
+
+impl PlayerIdentity {
+    /// Group slot occupancies into identities.
+    ///
+    /// Bots don't have a unique steam id, so every bot occupancy becomes its own identity
+    pub fn group(occupancies: &[SlotOccupancy]) -> Vec<PlayerIdentity> {
+        let mut identities: Vec<PlayerIdentity> = Vec::new();
+        for (occupancy, index) in occupancies.iter().zip(Self::indexes(occupancies)) {
+            match identities.get_mut(index) {
+                Some(identity) => {
+                    identity.name = occupancy.name.clone();
+                    identity.occupancies.push(occupancy.clone());
+                }
+                None => identities.push(PlayerIdentity {
+                    steam_id: occupancy.steam_id.clone(),
+                    name: occupancy.name.clone(),
+                    occupancies: vec![occupancy.clone()],
+                }),
+            }
+        }
+        identities
+    }
+
+    /// Get the index of the identity each occupancy belongs to, matching the order from `group`
+    pub fn indexes(occupancies: &[SlotOccupancy]) -> Vec<usize> {
+        // steam id for each identity, `None` for bots
+        let mut steam_ids: Vec<Option<&str>> = Vec::new();
+        occupancies
+            .iter()
+            .map(|occupancy| {
+                let is_bot = occupancy.steam_id.is_empty() || occupancy.steam_id == "BOT";
+                let steam_id = (!is_bot).then_some(occupancy.steam_id.as_str());
+                match steam_ids
+                    .iter()
+                    .position(|existing| steam_id.is_some() && *existing == steam_id)
+                {
+                    Some(index) => index,
+                    None => {
+                        steam_ids.push(steam_id);
+                        steam_ids.len() - 1
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_name_at() {
+    let history = [
+        NameChange {
+            tick: DemoTick::from(10),
+            name: "foo".into(),
+        },
+        NameChange {
+            tick: DemoTick::from(100),
+            name: "bar".into(),
+        },
+    ];
+
+    assert_eq!(Some("foo"), name_at(&history, DemoTick::from(0)));
+    assert_eq!(Some("foo"), name_at(&history, DemoTick::from(10)));
+    assert_eq!(Some("foo"), name_at(&history, DemoTick::from(99)));
+    assert_eq!(Some("bar"), name_at(&history, DemoTick::from(100)));
+    assert_eq!(Some("bar"), name_at(&history, DemoTick::from(1000)));
+    assert_eq!(None, name_at(&[], DemoTick::from(1000)));
+}
+
+#[test]
+fn test_group_identities() {
+    fn occupancy(slot: usize, user_id: u16, steam_id: &str, name: &str) -> SlotOccupancy {
+        SlotOccupancy {
+            slot,
+            user_id: UserId::from(user_id),
+            steam_id: steam_id.into(),
+            name: name.into(),
+            connect_tick: DemoTick::default(),
+            disconnect_tick: None,
+        }
+    }
+
+    let occupancies = [
+        occupancy(0, 2, "[U:1:1]", "foo"),
+        occupancy(1, 3, "BOT", "bot1"),
+        occupancy(2, 4, "BOT", "bot2"),
+        occupancy(3, 5, "[U:1:1]", "foo reconnected"),
+    ];
+
+    let identities = PlayerIdentity::group(&occupancies);
+    assert_eq!(3, identities.len());
+    assert_eq!("foo reconnected", identities[0].name);
+    assert_eq!(
+        vec![0, 3],
+        identities[0]
+            .occupancies
+            .iter()
+            .map(|occupancy| occupancy.slot)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!("bot1", identities[1].name);
+    assert_eq!("bot2", identities[2].name);
+    assert_eq!(vec![0, 1, 2, 0], PlayerIdentity::indexes(&occupancies));
+}
@@ -0,0 +1,288 @@
+use tf_demo_parser::demo::data::DemoTick;
+use tf_demo_parser::demo::gamevent::PlayerDeathEvent;
+use tf_demo_parser::demo::parser::gamestateanalyser::{Class, Team};
+use tf_demo_parser::demo::vector::Vector;
+
+/// `custom_kill` values from the death event we derive flags from
+const CUSTOM_KILL_HEADSHOT: u16 = 1;
+const CUSTOM_KILL_BACKSTAB: u16 = 2;
+const CUSTOM_KILL_HEADSHOT_DECAPITATION: u16 = 51;
+
+/// `death_flags` bits from the death event
+const DEATH_FLAG_DOMINATION: u16 = 1 << 0;
+const DEATH_FLAG_ASSISTER_DOMINATION: u16 = 1 << 1;
+const DEATH_FLAG_REVENGE: u16 = 1 << 2;
+const DEATH_FLAG_ASSISTER_REVENGE: u16 = 1 << 3;
+const DEATH_FLAG_FIRST_BLOOD: u16 = 1 << 4;
+const DEATH_FLAG_FEIGN_DEATH: u16 = 1 << 5;
+
+/// Flags for a kill, as exposed to js
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KillFlags(pub u16);
+
+impl KillFlags {
+    pub const HEADSHOT: u16 = 1 << 0;
+    pub const BACKSTAB: u16 = 1 << 1;
+    pub const AIRSHOT: u16 = 1 << 2;
+    pub const DOMINATION: u16 = 1 << 3;
+    pub const ASSISTER_DOMINATION: u16 = 1 << 4;
+    pub const REVENGE: u16 = 1 << 5;
+    pub const ASSISTER_REVENGE: u16 = 1 << 6;
+    pub const FIRST_BLOOD: u16 = 1 << 7;
+    pub const FEIGN_DEATH: u16 = 1 << 8;
+
+    pub fn new(custom_kill: u16, death_flags: u16, victim_airborne: bool) -> Self {
+        let mut flags = 0;
+        if matches!(
+            custom_kill,
+            CUSTOM_KILL_HEADSHOT | CUSTOM_KILL_HEADSHOT_DECAPITATION
+        ) {
+            flags |= Self::HEADSHOT;
+        }
+        if custom_kill == CUSTOM_KILL_BACKSTAB {
+            flags |= Self::BACKSTAB;
+        }
+        if victim_airborne {
+            flags |= Self::AIRSHOT;
+        }
+
+        let death_flag_map = [
+            (DEATH_FLAG_DOMINATION, Self::DOMINATION),
+            (DEATH_FLAG_ASSISTER_DOMINATION, Self::ASSISTER_DOMINATION),
+            (DEATH_FLAG_REVENGE, Self::REVENGE),
+            (DEATH_FLAG_ASSISTER_REVENGE, Self::ASSISTER_REVENGE),
+            (DEATH_FLAG_FIRST_BLOOD, Self::FIRST_BLOOD),
+            (DEATH_FLAG_FEIGN_DEATH, Self::FEIGN_DEATH),
+        ];
+        for (death_flag, flag) in death_flag_map {
+            if death_flags & death_flag != 0 {
+                flags |= flag;
+            }
+        }
+
+        KillFlags(flags)
+    }
+
+    pub fn contains(&self, flag: u16) -> bool {
+        self.0 & flag == flag
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum CritType {
+    #[default]
+    None = 0,
+    MiniCrit = 1,
+    Crit = 2,
+}
+
+impl From<u16> for CritType {
+    fn from(raw: u16) -> Self {
+        match raw {
+            1 => CritType::MiniCrit,
+            2 => CritType::Crit,
+            _ => CritType::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct KillRecord {
+    pub tick: DemoTick,
+    pub attacker_id: u16,
+    pub assister_id: u16,
+    pub victim_id: u16,
+    pub weapon: String,
+    pub custom_kill: u16,
+    pub crit_type: CritType,
+    pub flags: KillFlags,
+    /// World position of the attacker at the time of the kill, `None` for world kills
+    pub attacker_position: Option<Vector>,
+    pub victim_position: Option<Vector>,
+    /// Player slot of the attacker, `None` for world kills
+    pub attacker_slot: Option<usize>,
+    pub victim_slot: Option<usize>,
+    pub attacker_team: Team,
+    pub attacker_class: Class,
+    pub victim_team: Team,
+    pub victim_class: Class,
+}
+
+impl KillRecord {
+    pub fn new(tick: DemoTick, death: &PlayerDeathEvent, victim_airborne: bool) -> Self {
+        KillRecord {
+            tick,
+            attacker_id: death.attacker,
+            assister_id: death.assister,
+            victim_id: death.user_id,
+            weapon: death.weapon.to_string(),
+            custom_kill: death.custom_kill,
+            crit_type: CritType::from(death.crit_type),
+            flags: KillFlags::new(death.custom_kill, death.death_flags, victim_airborne),
+            attacker_position: None,
+            victim_position: None,
+            attacker_slot: None,
+            victim_slot: None,
+            attacker_team: Team::default(),
+            attacker_class: Class::default(),
+            victim_team: Team::default(),
+            victim_class: Class::default(),
+        }
+    }
+
+    /// Distance between the attacker and victim at the time of the kill
+    pub fn distance(&self) -> Option<f32> {
+        let attacker = self.attacker_position?;
+        let victim = self.victim_position?;
+        let (dx, dy, dz) = (
+            attacker.x - victim.x,
+            attacker.y - victim.y,
+            attacker.z - victim.z,
+        );
+        Some((dx * dx + dy * dy + dz * dz).sqrt())
+    }
+}
+
+/// Flatten optional positions into `[x, y, z, x, y, z, ...]` with `NaN` for missing positions
+pub fn flatten_positions(positions: impl Iterator<Item = Option<Vector>>) -> Box<[f32]> {
+    positions
+        .flat_map(|position| match position {
+            Some(position) => [position.x, position.y, position.z],
+            None => [f32::NAN; 3],
+        })
+        .collect()
+}
+
+/// Guess whether a player is airborne from their vertical movement.
+///
+/// We don't get the ground entity for players, so instead we consider a player airborne once
+/// their vertical speed has been dropping like it does under gravity for a while and they are a
+/// decent distance away from the last height they were standing still at. Walking on ramps or
+/// stairs changes the height at a steady rate, so that doesn't count as airborne.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AirborneTracker {
+    last_tick: u32,
+    last_z: f32,
+    /// Vertical speed in units per tick
+    last_speed: f32,
+    ground_z: f32,
+    /// Number of ticks the player has been standing still
+    stationary_ticks: u32,
+    /// Number of ticks the vertical speed has been dropping
+    falling_ticks: u32,
+}
+
+impl AirborneTracker {
+    const MIN_GROUND_TICKS: u32 = 5;
+    const MIN_FALLING_TICKS: u32 = 10;
+    const MIN_HEIGHT: f32 = 32.0;
+    /// Gravity slows a player down by about 0.18 units per tick each tick, we accept anything
+    /// above a fraction of that as falling
+    const MIN_DECELERATION: f32 = 0.05;
+
+    pub fn update(&mut self, tick: u32, z: f32) {
+        let ticks = tick.saturating_sub(self.last_tick);
+        if ticks == 0 {
+            return;
+        }
+        let speed = (z - self.last_z) / ticks as f32;
+
+        if (z - self.last_z).abs() < 0.1 {
+            self.stationary_ticks += ticks;
+        } else {
+            self.stationary_ticks = 0;
+        }
+
+        if speed < self.last_speed - Self::MIN_DECELERATION * ticks as f32 {
+            self.falling_ticks += ticks;
+        } else {
+            self.falling_ticks = 0;
+        }
+
+        // a single update without vertical movement happens at the apex of every jump, so we
+        // only treat the height as ground once the player stood still for a bit
+        if self.stationary_ticks >= Self::MIN_GROUND_TICKS {
+            self.ground_z = z;
+            self.falling_ticks = 0;
+        }
+
+        self.last_tick = tick;
+        self.last_z = z;
+        self.last_speed = speed;
+    }
+
+    pub fn is_airborne(&self) -> bool {
+        self.falling_ticks >= Self::MIN_FALLING_TICKS
+            && (self.last_z - self.ground_z).abs() > Self::MIN_HEIGHT
+    }
+}
+
+#[test]
+fn test_kill_flags() {
+    let flags = KillFlags::new(
+        CUSTOM_KILL_HEADSHOT,
+        DEATH_FLAG_DOMINATION | DEATH_FLAG_ASSISTER_REVENGE,
+        true,
+    );
+    assert!(flags.contains(KillFlags::HEADSHOT));
+    assert!(flags.contains(KillFlags::AIRSHOT));
+    assert!(flags.contains(KillFlags::DOMINATION));
+    assert!(flags.contains(KillFlags::ASSISTER_REVENGE));
+    assert!(!flags.contains(KillFlags::BACKSTAB));
+    assert!(!flags.contains(KillFlags::REVENGE));
+    assert!(!flags.contains(KillFlags::FIRST_BLOOD));
+}
+
+#[test]
+fn test_airborne_tracker() {
+    // gravity in units per tick per tick
+    const GRAVITY: f32 = 0.184;
+
+    let mut tracker = AirborneTracker::default();
+    for tick in (0..=20).step_by(2) {
+        tracker.update(tick, 100.0);
+    }
+    assert!(!tracker.is_airborne());
+
+    // rocket jump from tick 20, with an update every other tick
+    let jump = |tick: u32| {
+        let t = (tick - 20) as f32;
+        100.0 + 15.0 * t - 0.5 * GRAVITY * t * t
+    };
+    for tick in (22..=30).step_by(2) {
+        tracker.update(tick, jump(tick));
+    }
+    // only 8 ticks of falling so far
+    assert!(!tracker.is_airborne());
+    tracker.update(32, jump(32));
+    assert!(tracker.is_airborne());
+
+    // apex
+    for tick in (34..=102).step_by(2) {
+        tracker.update(tick, jump(tick));
+    }
+    assert!(tracker.is_airborne());
+
+    // 0.3s after the apex
+    for tick in (104..=122).step_by(2) {
+        tracker.update(tick, jump(tick));
+    }
+    assert!(tracker.is_airborne());
+
+    // landed
+    for tick in (124..=134).step_by(2) {
+        tracker.update(tick, 100.0);
+    }
+    assert!(!tracker.is_airborne());
+
+    // walking up and down a ramp
+    for tick in (136..=216).step_by(2) {
+        tracker.update(tick, 100.0 + (tick - 134) as f32 * 3.0);
+    }
+    assert!(!tracker.is_airborne());
+    for tick in (218..=298).step_by(2) {
+        tracker.update(tick, 346.0 - (tick - 216) as f32 * 3.0);
+    }
+    assert!(!tracker.is_airborne());
+}
@@ -1,6 +1,22 @@
 #![macro_use]
 
-use crate::state::{ParsedDemo, SearchableEvent};
+use crate::damage::{DamageRecord, DeflectRecord, HealRecord};
+use crate::heatmap::{kill_heatmap, occupancy_heatmap, HeatmapFilter};
+use crate::history::entry_at;
+use crate::identity::{name_at, NameChange, PlayerIdentity};
+use crate::kill::{flatten_positions, KillRecord};
+use crate::loadout::{weapon_at, Loadout, WeaponChange};
+use crate::objective::ObjectiveState;
+#[cfg(feature = "resources")]
+use crate::resources::ResourceSample;
+use crate::rounds::{score_at, Score, Segment};
+use crate::scoreboard::ScoreboardSample;
+use crate::sentry::{sentry_at, SentryChange};
+use crate::state::{BuildingState, ParsedDemo, PlayerState, ProjectileState, SearchableEvent};
+use crate::stats::PlayerStats;
+use crate::sticky::{traps_at, StickyTrap};
+use crate::teleporter::{exit_at, TeleporterLink};
+use crate::uber::ChargeCycle;
 use js_sys::Function;
 use tf_demo_parser::demo::header::Header;
 use tf_demo_parser::demo::parser::analyser::UserInfo;
@@ -9,7 +25,25 @@ use tf_demo_parser::demo::vector::Vector;
 use tf_demo_parser::{Demo, DemoParser, ParseError};
 use wasm_bindgen::prelude::*;
 
+mod columns;
+mod damage;
+mod heatmap;
+mod history;
+mod identity;
+mod kill;
+mod loadout;
+mod objective;
+// always compiled so the tests run without the feature, only the recording is feature gated
+#[cfg_attr(not(feature = "resources"), allow(dead_code))]
+mod resources;
+mod rounds;
+mod scoreboard;
+mod sentry;
 mod state;
+mod stats;
+mod sticky;
+mod teleporter;
+mod uber;
 
 #[wasm_bindgen]
 #[derive(Debug, Clone, Copy)]
@@ -40,11 +74,57 @@ impl From<World> for WorldBoundaries {
     }
 }
 
+impl From<WorldBoundaries> for World {
+    fn from(boundaries: WorldBoundaries) -> Self {
+        World {
+            boundary_min: Vector {
+                x: boundaries.boundary_min.x,
+                y: boundaries.boundary_min.y,
+                z: 0.0,
+            },
+            boundary_max: Vector {
+                x: boundaries.boundary_max.x,
+                y: boundaries.boundary_max.y,
+                z: 0.0,
+            },
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy)]
+pub struct TeamScore {
+    pub red: u16,
+    pub blue: u16,
+}
+
+impl From<Score> for TeamScore {
+    fn from(score: Score) -> Self {
+        TeamScore {
+            red: score.red,
+            blue: score.blue,
+        }
+    }
+}
+
 #[wasm_bindgen]
 pub struct FlatState {
     pub player_count: usize,
     pub building_count: usize,
     pub projectile_count: usize,
+    /// Number of control points used in the objective column that follows the projectiles
+    ///
+    /// Point owners and the cart position are read from the entities, the capture progress is an
+    /// estimate
+    pub control_point_count: usize,
+    /// Size of a single tick in the objective column
+    pub objective_packet_size: usize,
+    /// Version of the player packet layout
+    pub player_packet_version: u8,
+    /// Version of the building packet layout
+    pub building_packet_version: u8,
+    /// Version of the projectile packet layout
+    pub projectile_packet_version: u8,
     pub boundaries: WorldBoundaries,
     pub interval_per_tick: f32,
     pub tick_count: u32,
@@ -53,14 +133,45 @@ pub struct FlatState {
     assisters: Box<[u8]>,
     victims: Box<[u8]>,
     weapons: Vec<String>,
-    player_info: Vec<UserInfo>,
+    kill_custom_types: Box<[u16]>,
+    kill_crit_types: Box<[u8]>,
+    kill_flags: Box<[u16]>,
+    attacker_positions: Box<[f32]>,
+    victim_positions: Box<[f32]>,
+    kill_distances: Box<[f32]>,
+    kills: Vec<KillRecord>,
+    damage: Vec<DamageRecord>,
+    healing: Vec<HealRecord>,
+    deflects: Vec<DeflectRecord>,
+    player_info: Vec<Option<UserInfo>>,
     events: Vec<SearchableEvent>,
+    identities: Vec<PlayerIdentity>,
+    name_changes: Vec<Vec<NameChange>>,
+    player_stats: Vec<PlayerStats>,
+    identity_stats: Vec<PlayerStats>,
+    charge_cycles: Vec<Vec<ChargeCycle>>,
+    segments: Vec<Segment>,
+    scores: Vec<Score>,
+    scoreboard: Vec<ScoreboardSample>,
+    weapon_changes: Vec<Vec<WeaponChange>>,
+    loadouts: Vec<Vec<Loadout>>,
+    sticky_traps: Vec<StickyTrap>,
+    sentry_changes: Vec<Vec<SentryChange>>,
+    teleporter_links: Vec<Vec<TeleporterLink>>,
+    #[cfg(feature = "resources")]
+    resources: Vec<ResourceSample>,
     data: Box<[u8]>,
     header: Header,
 }
 
 impl FlatState {
     pub fn new(parsed: ParsedDemo, world: World) -> Self {
+        let occupancy_stats = PlayerStats::collect(&parsed, &world);
+        let player_stats =
+            PlayerStats::per_slot(&occupancy_stats, &parsed.occupancies, parsed.players.len());
+        let identity_stats = PlayerStats::per_identity(&occupancy_stats, &parsed.occupancies);
+        let charge_cycles = ChargeCycle::collect(&parsed, &world);
+
         let ParsedDemo {
             players,
             header,
@@ -69,6 +180,8 @@ impl FlatState {
             max_building_count,
             max_projectile_count,
             tick,
+            objectives,
+            objective,
             ..
         } = parsed;
 
@@ -80,6 +193,7 @@ impl FlatState {
             .into_iter()
             .chain(buildings)
             .chain(projectiles)
+            .chain(std::iter::once(objectives))
             .flat_map(Vec::into_iter)
             .collect();
 
@@ -87,6 +201,11 @@ impl FlatState {
             player_count,
             building_count,
             projectile_count,
+            control_point_count: objective.control_point_count,
+            objective_packet_size: ObjectiveState::PACKET_SIZE,
+            player_packet_version: PlayerState::PACKET_VERSION,
+            building_packet_version: BuildingState::PACKET_VERSION,
+            projectile_packet_version: ProjectileState::PACKET_VERSION,
             tick_count: tick as u32,
             boundaries: world.into(),
             interval_per_tick: header.duration / (header.ticks as f32),
@@ -107,12 +226,57 @@ impl FlatState {
                 .iter()
                 .map(|kill| kill.victim_id as u8)
                 .collect(),
-            weapons: parsed.kills.into_iter().map(|kill| kill.weapon).collect(),
+            kill_custom_types: parsed.kills.iter().map(|kill| kill.custom_kill).collect(),
+            kill_crit_types: parsed
+                .kills
+                .iter()
+                .map(|kill| kill.crit_type as u8)
+                .collect(),
+            kill_flags: parsed.kills.iter().map(|kill| kill.flags.0).collect(),
+            attacker_positions: flatten_positions(
+                parsed.kills.iter().map(|kill| kill.attacker_position),
+            ),
+            victim_positions: flatten_positions(
+                parsed.kills.iter().map(|kill| kill.victim_position),
+            ),
+            kill_distances: parsed
+                .kills
+                .iter()
+                .map(|kill| kill.distance().unwrap_or(f32::NAN))
+                .collect(),
+            weapons: parsed
+                .kills
+                .iter()
+                .map(|kill| kill.weapon.clone())
+                .collect(),
+            kills: parsed.kills,
+            damage: parsed.damage,
+            healing: parsed.healing,
+            deflects: parsed.deflects,
             player_info: parsed.player_info,
             events: parsed.events,
+            identities: parsed.identities,
+            name_changes: parsed.name_changes,
+            player_stats,
+            identity_stats,
+            charge_cycles,
+            segments: parsed.segments,
+            scores: parsed.scores,
+            scoreboard: parsed.scoreboard.samples,
+            weapon_changes: parsed.loadouts.weapon_changes,
+            loadouts: parsed.loadouts.loadouts,
+            sticky_traps: parsed.stickies.traps,
+            sentry_changes: parsed.sentries.changes,
+            teleporter_links: parsed.teleporters.links,
+            #[cfg(feature = "resources")]
+            resources: parsed.resources.samples,
             header,
         }
     }
+
+    fn player_info(&self, player_id: usize) -> Option<&UserInfo> {
+        self.player_info.get(player_id).and_then(Option::as_ref)
+    }
 }
 
 #[wasm_bindgen]
@@ -155,29 +319,249 @@ pub fn get_victim_ids(state: &FlatState) -> Box<[u8]> {
     state.victims.clone()
 }
 
+/// World positions of the attacker for every kill as `[x, y, z, ...]`, `NaN` if unknown
+#[wasm_bindgen]
+pub fn get_attacker_positions(state: &FlatState) -> Box<[f32]> {
+    state.attacker_positions.clone()
+}
+
+/// World positions of the victim for every kill as `[x, y, z, ...]`, `NaN` if unknown
+#[wasm_bindgen]
+pub fn get_victim_positions(state: &FlatState) -> Box<[f32]> {
+    state.victim_positions.clone()
+}
+
+/// Distance between attacker and victim for every kill, `NaN` if unknown
+#[wasm_bindgen]
+pub fn get_kill_distances(state: &FlatState) -> Box<[f32]> {
+    state.kill_distances.clone()
+}
+
+/// Get a `width` x `height` grid over the world boundaries with the number of kills or deaths
+/// matching the filter in each cell
+#[wasm_bindgen]
+pub fn get_kill_heatmap(
+    state: &FlatState,
+    filter: &HeatmapFilter,
+    width: usize,
+    height: usize,
+) -> Box<[f32]> {
+    kill_heatmap(&state.kills, state.boundaries, filter, width, height)
+}
+
+/// Get a `width` x `height` grid over the world boundaries with the number of seconds alive
+/// players matching the filter spent in each cell
+///
+/// The heatmap is computed from the packed player data, so this has to be called before
+/// `get_data` takes the data out of the state
+#[wasm_bindgen]
+pub fn get_occupancy_heatmap(
+    state: &FlatState,
+    filter: &HeatmapFilter,
+    width: usize,
+    height: usize,
+) -> Box<[f32]> {
+    occupancy_heatmap(state, filter, width, height)
+}
+
 #[wasm_bindgen]
 pub fn get_weapon(state: &FlatState, kill_id: usize) -> String {
     state.weapons[kill_id].clone()
 }
 
+/// The `custom_kill` value from the death event for every kill
+#[wasm_bindgen]
+pub fn get_kill_custom_types(state: &FlatState) -> Box<[u16]> {
+    state.kill_custom_types.clone()
+}
+
+/// 0 for no crit, 1 for mini-crit and 2 for full crit for every kill
+#[wasm_bindgen]
+pub fn get_kill_crit_types(state: &FlatState) -> Box<[u8]> {
+    state.kill_crit_types.clone()
+}
+
+/// Headshot, backstab, airshot, domination, etc flags for every kill, see `KillFlags`
+#[wasm_bindgen]
+pub fn get_kill_flags(state: &FlatState) -> Box<[u16]> {
+    state.kill_flags.clone()
+}
+
+#[wasm_bindgen]
+pub fn get_damage_ticks(state: &FlatState) -> Box<[u32]> {
+    state
+        .damage
+        .iter()
+        .map(|damage| damage.tick.into())
+        .collect()
+}
+
+#[wasm_bindgen]
+pub fn get_damage_attacker_ids(state: &FlatState) -> Box<[u16]> {
+    state
+        .damage
+        .iter()
+        .map(|damage| damage.attacker_id)
+        .collect()
+}
+
+#[wasm_bindgen]
+pub fn get_damage_victim_ids(state: &FlatState) -> Box<[u16]> {
+    state.damage.iter().map(|damage| damage.victim_id).collect()
+}
+
+#[wasm_bindgen]
+pub fn get_damage_amounts(state: &FlatState) -> Box<[u16]> {
+    state.damage.iter().map(|damage| damage.damage).collect()
+}
+
+/// Health of the victim after every damage event
+#[wasm_bindgen]
+pub fn get_damage_victim_health(state: &FlatState) -> Box<[u16]> {
+    state
+        .damage
+        .iter()
+        .map(|damage| damage.victim_health)
+        .collect()
+}
+
+#[wasm_bindgen]
+pub fn get_damage_weapon_ids(state: &FlatState) -> Box<[u16]> {
+    state.damage.iter().map(|damage| damage.weapon_id).collect()
+}
+
+/// 0 for no crit, 1 for mini-crit and 2 for full crit for every damage event
+#[wasm_bindgen]
+pub fn get_damage_crit_types(state: &FlatState) -> Box<[u8]> {
+    state
+        .damage
+        .iter()
+        .map(|damage| damage.crit_type as u8)
+        .collect()
+}
+
+#[wasm_bindgen]
+pub fn get_heal_ticks(state: &FlatState) -> Box<[u32]> {
+    state.healing.iter().map(|heal| heal.tick.into()).collect()
+}
+
+#[wasm_bindgen]
+pub fn get_healer_ids(state: &FlatState) -> Box<[u16]> {
+    state.healing.iter().map(|heal| heal.healer_id).collect()
+}
+
+#[wasm_bindgen]
+pub fn get_heal_patient_ids(state: &FlatState) -> Box<[u16]> {
+    state.healing.iter().map(|heal| heal.patient_id).collect()
+}
+
+#[wasm_bindgen]
+pub fn get_heal_amounts(state: &FlatState) -> Box<[u16]> {
+    state.healing.iter().map(|heal| heal.amount).collect()
+}
+
+#[wasm_bindgen]
+pub fn get_deflect_ticks(state: &FlatState) -> Box<[u32]> {
+    state
+        .deflects
+        .iter()
+        .map(|deflect| deflect.tick.into())
+        .collect()
+}
+
+#[wasm_bindgen]
+pub fn get_deflector_ids(state: &FlatState) -> Box<[u16]> {
+    state
+        .deflects
+        .iter()
+        .map(|deflect| deflect.deflector_id)
+        .collect()
+}
+
+#[wasm_bindgen]
+pub fn get_deflect_owner_ids(state: &FlatState) -> Box<[u16]> {
+    state
+        .deflects
+        .iter()
+        .map(|deflect| deflect.owner_id)
+        .collect()
+}
+
+#[wasm_bindgen]
+pub fn get_deflect_weapon_ids(state: &FlatState) -> Box<[u16]> {
+    state
+        .deflects
+        .iter()
+        .map(|deflect| deflect.weapon_id)
+        .collect()
+}
+
+/// Entity ids of the reflected projectiles
+#[wasm_bindgen]
+pub fn get_deflect_projectile_ids(state: &FlatState) -> Box<[u16]> {
+    state
+        .deflects
+        .iter()
+        .map(|deflect| deflect.projectile_entity)
+        .collect()
+}
+
+/// The team the projectiles belong to after being reflected
+#[wasm_bindgen]
+pub fn get_deflect_teams(state: &FlatState) -> Box<[u8]> {
+    state
+        .deflects
+        .iter()
+        .map(|deflect| deflect.team as u8)
+        .collect()
+}
+
 #[wasm_bindgen]
 pub fn get_player_name(state: &FlatState, player_id: usize) -> String {
-    state.player_info[player_id].name.clone()
+    state
+        .player_info(player_id)
+        .map(|info| info.name.clone())
+        .unwrap_or_default()
+}
+
+#[wasm_bindgen]
+pub fn get_player_name_at(state: &FlatState, player_id: usize, tick: u32) -> String {
+    state
+        .name_changes
+        .get(player_id)
+        .and_then(|history| name_at(history, tick.into()))
+        .map(String::from)
+        .unwrap_or_else(|| get_player_name(state, player_id))
+}
+
+/// Get all names a player used during the demo with the tick they switched to it, as json
+#[wasm_bindgen]
+pub fn get_player_name_changes(state: &FlatState, player_id: usize) -> String {
+    serde_json::to_string(&state.name_changes.get(player_id)).unwrap_or_default()
 }
 
 #[wasm_bindgen]
 pub fn get_player_entity_id(state: &FlatState, player_id: usize) -> u32 {
-    state.player_info[player_id].entity_id.into()
+    state
+        .player_info(player_id)
+        .map(|info| info.entity_id.into())
+        .unwrap_or_default()
 }
 
 #[wasm_bindgen]
 pub fn get_player_user_id(state: &FlatState, player_id: usize) -> u16 {
-    state.player_info[player_id].user_id.into()
+    state
+        .player_info(player_id)
+        .map(|info| info.user_id.into())
+        .unwrap_or_default()
 }
 
 #[wasm_bindgen]
 pub fn get_player_steam_id(state: &FlatState, player_id: usize) -> String {
-    state.player_info[player_id].steam_id.clone()
+    state
+        .player_info(player_id)
+        .map(|info| info.steam_id.clone())
+        .unwrap_or_default()
 }
 
 #[wasm_bindgen]
@@ -190,6 +574,141 @@ pub fn get_event(state: &FlatState, id: usize) -> String {
     serde_json::to_string(&state.events[id]).unwrap_or_default()
 }
 
+#[wasm_bindgen]
+pub fn get_identity_count(state: &FlatState) -> usize {
+    state.identities.len()
+}
+
+/// Get a player identity, merging all slots a player occupied by steam id, as json
+#[wasm_bindgen]
+pub fn get_identity(state: &FlatState, id: usize) -> String {
+    serde_json::to_string(&state.identities[id]).unwrap_or_default()
+}
+
+/// Get the kills, deaths, assists, ubers, etc for a player slot, as json
+///
+/// A slot can be used by multiple players over the demo, use `get_identity_stats` for the
+/// totals of a single player
+#[wasm_bindgen]
+pub fn get_player_stats(state: &FlatState, player_id: usize) -> String {
+    serde_json::to_string(&state.player_stats.get(player_id)).unwrap_or_default()
+}
+
+/// Get the kills, deaths, assists, ubers, etc for a player identity, including every slot they
+/// occupied, as json
+#[wasm_bindgen]
+pub fn get_identity_stats(state: &FlatState, identity_id: usize) -> String {
+    serde_json::to_string(&state.identity_stats.get(identity_id)).unwrap_or_default()
+}
+
+/// Get the charge build, deploy and drops for a medic player slot, as json
+#[wasm_bindgen]
+pub fn get_charge_cycles(state: &FlatState, player_id: usize) -> String {
+    serde_json::to_string(&state.charge_cycles.get(player_id)).unwrap_or_default()
+}
+
+#[wasm_bindgen]
+pub fn get_segment_count(state: &FlatState) -> usize {
+    state.segments.len()
+}
+
+/// Get a round, setup, humiliation, etc segment of the demo with its start and end tick, as json
+#[wasm_bindgen]
+pub fn get_segment(state: &FlatState, id: usize) -> String {
+    serde_json::to_string(&state.segments[id]).unwrap_or_default()
+}
+
+/// Get the team scores at a tick
+///
+/// Scores come from the team entities when the demo has them, otherwise they are counted from
+/// the round wins in the demo, which misses any rounds played before the recording started
+#[wasm_bindgen]
+pub fn get_score_at(state: &FlatState, tick: u32) -> TeamScore {
+    score_at(&state.scores, tick.into()).into()
+}
+
+/// Get the ping, score, connection state and kills/deaths/assists for every player slot at a tick,
+/// as json
+///
+/// The scoreboard is sampled about once per second
+#[wasm_bindgen]
+pub fn get_scoreboard_at(state: &FlatState, tick: u32) -> String {
+    serde_json::to_string(&entry_at(&state.scoreboard, tick.into())).unwrap_or_default()
+}
+
+/// Get the item definition index of the weapon a player was holding at a tick
+#[wasm_bindgen]
+pub fn get_active_weapon_at(state: &FlatState, player_id: usize, tick: u32) -> Option<u32> {
+    state
+        .weapon_changes
+        .get(player_id)
+        .and_then(|history| weapon_at(history, tick.into()))
+}
+
+/// Get all active weapon switches for a player as json
+#[wasm_bindgen]
+pub fn get_active_weapon_changes(state: &FlatState, player_id: usize) -> String {
+    serde_json::to_string(&state.weapon_changes.get(player_id)).unwrap_or_default()
+}
+
+/// Get the equipped items for every life of a player as json
+#[wasm_bindgen]
+pub fn get_loadouts(state: &FlatState, player_id: usize) -> String {
+    serde_json::to_string(&state.loadouts.get(player_id)).unwrap_or_default()
+}
+
+/// Get all sticky traps placed during the demo as json
+#[wasm_bindgen]
+pub fn get_sticky_traps(state: &FlatState) -> String {
+    serde_json::to_string(&state.sticky_traps).unwrap_or_default()
+}
+
+/// Get the sticky traps that are placed at a tick as json
+#[wasm_bindgen]
+pub fn get_sticky_traps_at(state: &FlatState, tick: u32) -> String {
+    serde_json::to_string(&traps_at(&state.sticky_traps, tick.into())).unwrap_or_default()
+}
+
+/// Get the target, ammo and wrangled state of the sentry in a building column at a tick, as json
+///
+/// Returns `null` when the column doesn't hold a sentry at that tick
+#[wasm_bindgen]
+pub fn get_sentry_at(state: &FlatState, building_id: usize, tick: u32) -> String {
+    let sentry = state
+        .sentry_changes
+        .get(building_id)
+        .and_then(|history| sentry_at(history, tick.into()));
+    serde_json::to_string(&sentry).unwrap_or_default()
+}
+
+/// Get all target, ammo and wrangled changes for the sentries in a building column as json
+///
+/// The state is `null` while the column doesn't hold a sentry
+#[wasm_bindgen]
+pub fn get_sentry_changes(state: &FlatState, building_id: usize) -> String {
+    serde_json::to_string(&state.sentry_changes.get(building_id)).unwrap_or_default()
+}
+
+/// Get the building column of the exit linked to a teleporter entrance at a tick
+#[wasm_bindgen]
+pub fn get_teleporter_exit_at(state: &FlatState, building_id: usize, tick: u32) -> Option<u32> {
+    state
+        .teleporter_links
+        .get(building_id)
+        .and_then(|history| exit_at(history, tick.into()))
+        .map(|exit| exit as u32)
+}
+
+/// Get the metal, clip and reserve ammo for every player slot at a tick, as json
+///
+/// Only available when built with the `resources` feature, the counts are sampled about once per
+/// second
+#[cfg(feature = "resources")]
+#[wasm_bindgen]
+pub fn get_resources_at(state: &FlatState, tick: u32) -> String {
+    serde_json::to_string(&entry_at(&state.resources, tick.into())).unwrap_or_default()
+}
+
 pub fn parse_demo_inner(
     buffer: &[u8],
     progress: &Function,
@@ -217,7 +736,6 @@ pub fn parse_demo_inner(
 
     let state = ticker.into_state();
 
-    parsed_demo.kills = state.kills;
     Ok((parsed_demo, state.world))
 }
 
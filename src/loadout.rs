@@ -0,0 +1,145 @@
+use crate::history::{entry_at, Timed};
+use serde::Serialize;
+use tf_demo_parser::demo::data::game_state::Handle;
+use tf_demo_parser::demo::data::DemoTick;
+use tf_demo_parser::demo::parser::gamestateanalyser::{GameState, Player, PlayerState};
+
+const GUNSLINGER: &[u32] = &[142];
+const WRANGLER: &[u32] = &[140, 1086, 30668];
+const ROCKET_JUMPER: &[u32] = &[237];
+const STICKY_JUMPER: &[u32] = &[265];
+
+/// The weapon a player slot switched to at a specific tick
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct WeaponChange {
+    pub tick: DemoTick,
+    /// Item definition index of the active weapon, `None` if the weapon entity isn't known
+    pub item_index: Option<u32>,
+}
+
+impl Timed for WeaponChange {
+    fn tick(&self) -> DemoTick {
+        self.tick
+    }
+}
+
+/// Get the active weapon a player had at a specific tick from its weapon history
+pub fn weapon_at(history: &[WeaponChange], tick: DemoTick) -> Option<u32> {
+    entry_at(history, tick).and_then(|change| change.item_index)
+}
+
+/// The items a player had equipped during a single life
+#[derive(Debug, Clone, Serialize)]
+pub struct Loadout {
+    /// The tick the player spawned
+    pub start_tick: DemoTick,
+    /// Item definition indexes of the equipped weapons
+    pub items: Vec<u32>,
+    pub gunslinger: bool,
+    pub wrangler: bool,
+    /// Rocket or sticky jumper
+    pub jumper: bool,
+}
+
+impl Loadout {
+    pub fn new(start_tick: DemoTick, items: Vec<u32>) -> Self {
+        let has = |indexes: &[u32]| items.iter().any(|item| indexes.contains(item));
+        Loadout {
+            start_tick,
+            gunslinger: has(GUNSLINGER),
+            wrangler: has(WRANGLER),
+            jumper: has(ROCKET_JUMPER) || has(STICKY_JUMPER),
+            items,
+        }
+    }
+}
+
+/// Follow the active weapon and equipped items of every player slot.
+///
+/// Weapon entities can show up a few ticks after the player spawned, and loadouts can be changed
+/// at a resupply cabinet without respawning, so the loadout of the current life is updated
+/// whenever the equipped items change.
+#[derive(Debug, Default)]
+pub struct LoadoutTracker {
+    alive: Vec<bool>,
+    pub weapon_changes: Vec<Vec<WeaponChange>>,
+    pub loadouts: Vec<Vec<Loadout>>,
+}
+
+impl LoadoutTracker {
+    pub fn update(&mut self, game_state: &GameState) {
+        let player_count = game_state.players.len();
+        if self.alive.len() < player_count {
+            self.alive.resize(player_count, false);
+            self.weapon_changes.resize_with(player_count, Vec::new);
+            self.loadouts.resize_with(player_count, Vec::new);
+        }
+
+        for (slot, player) in game_state.players.iter().enumerate() {
+            let alive = player.state == PlayerState::Alive;
+            let spawned = alive && !self.alive[slot];
+            self.alive[slot] = alive;
+            if !alive {
+                continue;
+            }
+
+            let active = item_index(game_state, player.active_weapon);
+            let history = &mut self.weapon_changes[slot];
+            if history.last().is_none_or(|last| last.item_index != active) {
+                history.push(WeaponChange {
+                    tick: game_state.tick,
+                    item_index: active,
+                });
+            }
+
+            let items = equipped_items(game_state, player);
+            let lives = &mut self.loadouts[slot];
+            match lives.last_mut() {
+                Some(current) if !spawned => {
+                    if current.items != items {
+                        *current = Loadout::new(current.start_tick, items);
+                    }
+                }
+                _ => lives.push(Loadout::new(game_state.tick, items)),
+            }
+        }
+    }
+}
+
+fn item_index(game_state: &GameState, handle: Handle) -> Option<u32> {
+    game_state.weapons.get(&handle).map(|weapon| weapon.id)
+}
+
+fn equipped_items(game_state: &GameState, player: &Player) -> Vec<u32> {
+    player
+        .weapons
+        .iter()
+        .filter_map(|handle| item_index(game_state, *handle))
+        .collect()
+}
+
+#[test]
+fn test_loadout() {
+    let engineer = Loadout::new(DemoTick::from(10u32), vec![9, 140, 142]);
+    assert!(engineer.gunslinger);
+    assert!(engineer.wrangler);
+    assert!(!engineer.jumper);
+
+    let soldier = Loadout::new(DemoTick::from(10u32), vec![237, 10, 6]);
+    assert!(soldier.jumper);
+    assert!(!soldier.gunslinger);
+
+    let history = [
+        WeaponChange {
+            tick: DemoTick::from(10u32),
+            item_index: Some(9),
+        },
+        WeaponChange {
+            tick: DemoTick::from(20u32),
+            item_index: Some(142),
+        },
+    ];
+    assert_eq!(None, weapon_at(&history, DemoTick::from(5u32)));
+    assert_eq!(Some(9), weapon_at(&history, DemoTick::from(15u32)));
+    assert_eq!(Some(142), weapon_at(&history, DemoTick::from(20u32)));
+}
@@ -0,0 +1,341 @@
+use crate::state::{pack_f32, unpack_f32};
+use tf_demo_parser::demo::gamevent::GameEvent;
+use tf_demo_parser::demo::parser::gamestateanalyser::{GameState, Team, World};
+use tf_demo_parser::demo::vector::VectorXY;
+
+pub const MAX_CONTROL_POINTS: usize = 8;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ControlPointState {
+    pub owner: Team,
+    /// The team currently capturing the point, `Team::Other` if the point isn't being captured
+    pub capping_team: Team,
+    /// Estimated capture progress, 0 to 255
+    pub progress: u8,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjectiveState {
+    pub control_points: [ControlPointState; MAX_CONTROL_POINTS],
+    /// Payload progress, 0 to 255
+    pub cart_progress: u8,
+    /// Position of the payload cart, `None` on maps without a cart
+    pub cart_position: Option<VectorXY>,
+}
+
+impl ObjectiveState {
+    pub const PACKET_SIZE: usize = MAX_CONTROL_POINTS * 2 + 6;
+
+    pub fn pack(&self, world: &World) -> [u8; Self::PACKET_SIZE] {
+        let mut bytes = [0; Self::PACKET_SIZE];
+        let (points, cart) = bytes.split_at_mut(MAX_CONTROL_POINTS * 2);
+        for (point, packed) in self.control_points.iter().zip(points.chunks_exact_mut(2)) {
+            // 2 bits for owner
+            // 2 bits for capping team
+            packed[0] = (point.owner as u8) + ((point.capping_team as u8) << 2);
+            packed[1] = point.progress;
+        }
+
+        // 1 byte progress
+        // 1 byte for whether there is a cart
+        // 2x2 bytes cart position
+        cart[0] = self.cart_progress;
+        if let Some(position) = &self.cart_position {
+            let x = pack_f32(position.x, world.boundary_min.x, world.boundary_max.x).to_le_bytes();
+            let y = pack_f32(position.y, world.boundary_min.y, world.boundary_max.y).to_le_bytes();
+            cart[1] = 1;
+            cart[2..].copy_from_slice(&[x[0], x[1], y[0], y[1]]);
+        }
+        bytes
+    }
+
+    #[allow(dead_code)]
+    pub fn unpack(bytes: [u8; Self::PACKET_SIZE], world: &World) -> Self {
+        let (points, cart) = bytes.split_at(MAX_CONTROL_POINTS * 2);
+        let mut control_points = [ControlPointState::default(); MAX_CONTROL_POINTS];
+        for (point, packed) in control_points.iter_mut().zip(points.chunks_exact(2)) {
+            point.owner = Team::new((packed[0] & 3) as u16);
+            point.capping_team = Team::new(((packed[0] >> 2) & 3) as u16);
+            point.progress = packed[1];
+        }
+
+        let cart_position = (cart[1] != 0).then(|| VectorXY {
+            x: unpack_f32(
+                u16::from_le_bytes([cart[2], cart[3]]),
+                world.boundary_min.x,
+                world.boundary_max.x,
+            ),
+            y: unpack_f32(
+                u16::from_le_bytes([cart[4], cart[5]]),
+                world.boundary_min.y,
+                world.boundary_max.y,
+            ),
+        });
+
+        ObjectiveState {
+            control_points,
+            cart_progress: cart[0],
+            cart_position,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Capture {
+    start_tick: usize,
+    /// Progress when the capture started, captures continue from where the last one was blocked
+    start_progress: f32,
+    /// Capture time in seconds, adjusted for the number of cappers
+    cap_time: f32,
+    /// The progress the capture was stuck at after being blocked
+    blocked_progress: Option<f32>,
+}
+
+impl Capture {
+    fn progress(&self, tick: usize, interval_per_tick: f32) -> f32 {
+        if let Some(progress) = self.blocked_progress {
+            return progress;
+        }
+        let elapsed = tick.saturating_sub(self.start_tick) as f32 * interval_per_tick;
+        let progress = if self.cap_time > 0.0 {
+            self.start_progress + elapsed / self.cap_time
+        } else {
+            self.start_progress
+        };
+        progress.clamp(0.0, 1.0)
+    }
+}
+
+/// Capture rate multiplier for a number of cappers, every extra capper adds less than the one
+/// before it
+fn capture_rate(cappers: usize) -> f32 {
+    (1..=cappers.max(1)).map(|capper| 1.0 / capper as f32).sum()
+}
+
+/// Follow the control point and payload state.
+///
+/// Point ownership and the cart position come from the entities. Capture progress is an estimate
+/// from the capture time the point reports when a capture starts and the number of players that
+/// started the capture, players joining or leaving the point afterwards and scouts capturing twice
+/// as fast aren't taken into account. Blocked captures stop progressing until they are broken or
+/// continued.
+#[derive(Debug, Default)]
+pub struct ObjectiveTracker {
+    state: ObjectiveState,
+    captures: [Option<Capture>; MAX_CONTROL_POINTS],
+    /// The number of control points we've seen
+    pub control_point_count: usize,
+}
+
+impl ObjectiveTracker {
+    /// Read the point owners and cart position from the entities
+    pub fn update(&mut self, game_state: &GameState) {
+        for (index, point) in game_state
+            .control_points
+            .iter()
+            .enumerate()
+            .take(MAX_CONTROL_POINTS)
+        {
+            self.track_point(index);
+            self.state.control_points[index].owner = point.owner;
+        }
+        self.state.cart_position = game_state.payload_carts.first().map(|cart| VectorXY {
+            x: cart.position.x,
+            y: cart.position.y,
+        });
+    }
+
+    pub fn handle_event(&mut self, tick: usize, event: &GameEvent) {
+        match event {
+            GameEvent::TeamPlayRoundStart(_) => {
+                for (point, capture) in self
+                    .state
+                    .control_points
+                    .iter_mut()
+                    .zip(self.captures.iter_mut())
+                {
+                    point.capping_team = Team::default();
+                    point.progress = 0;
+                    *capture = None;
+                }
+                self.state.cart_progress = 0;
+            }
+            GameEvent::TeamPlayPointStartCapture(event) => {
+                // one byte with the entity index for every player on the point
+                let cappers: &[u8] = event.cappers.as_ref();
+                self.start_capture(
+                    event.cp as usize,
+                    tick,
+                    Team::new(event.cap_team as u16),
+                    event.cap_time,
+                    cappers.len(),
+                );
+            }
+            GameEvent::TeamPlayCaptureBlocked(event) => {
+                let index = event.cp as usize;
+                if self.track_point(index) {
+                    let progress = self.state.control_points[index].progress as f32 / 255.0;
+                    if let Some(capture) = self.captures[index].as_mut() {
+                        capture.blocked_progress = Some(progress);
+                    }
+                }
+            }
+            GameEvent::TeamPlayPointCaptured(event) => {
+                let index = event.cp as usize;
+                if self.track_point(index) {
+                    self.state.control_points[index] = ControlPointState {
+                        owner: Team::new(event.team as u16),
+                        capping_team: Team::default(),
+                        progress: 0,
+                    };
+                    self.captures[index] = None;
+                }
+            }
+            GameEvent::TeamPlayCaptureBroken(event) => {
+                let index = event.cp as usize;
+                if self.track_point(index) {
+                    let point = &mut self.state.control_points[index];
+                    point.capping_team = Team::default();
+                    point.progress = 0;
+                    self.captures[index] = None;
+                }
+            }
+            GameEvent::EscortProgress(event) => {
+                self.state.cart_progress = (event.progress.clamp(0.0, 1.0) * 255.0) as u8;
+            }
+            _ => {}
+        }
+    }
+
+    fn start_capture(
+        &mut self,
+        index: usize,
+        tick: usize,
+        team: Team,
+        cap_time: f32,
+        cappers: usize,
+    ) {
+        if !self.track_point(index) {
+            return;
+        }
+        let point = &mut self.state.control_points[index];
+        // a blocked capture continues from where it was when the same team caps again
+        let start_progress = if self.captures[index].is_some() && point.capping_team == team {
+            point.progress as f32 / 255.0
+        } else {
+            0.0
+        };
+        point.capping_team = team;
+        self.captures[index] = Some(Capture {
+            start_tick: tick,
+            start_progress,
+            cap_time: cap_time / capture_rate(cappers),
+            blocked_progress: None,
+        });
+    }
+
+    /// Check that the control point index is valid and keep track of the number of points
+    fn track_point(&mut self, index: usize) -> bool {
+        if index >= MAX_CONTROL_POINTS {
+            return false;
+        }
+        self.control_point_count = self.control_point_count.max(index + 1);
+        true
+    }
+
+    /// Get the objective state for a tick, updating the capture progress estimates
+    pub fn state(&mut self, tick: usize, interval_per_tick: f32) -> &ObjectiveState {
+        for (point, capture) in self
+            .state
+            .control_points
+            .iter_mut()
+            .zip(self.captures.iter())
+        {
+            if let Some(capture) = capture {
+                point.progress = (capture.progress(tick, interval_per_tick) * 255.0) as u8;
+            }
+        }
+        &self.state
+    }
+}
+
+#[test]
+fn test_objective_packing() {
+    use tf_demo_parser::demo::vector::Vector;
+
+    let world = World {
+        boundary_max: Vector {
+            x: 10000.0,
+            y: 10000.0,
+            z: 100.0,
+        },
+        boundary_min: Vector {
+            x: -10000.0,
+            y: -10000.0,
+            z: -100.0,
+        },
+    };
+
+    let mut input = ObjectiveState::default();
+    input.control_points[0] = ControlPointState {
+        owner: Team::Red,
+        capping_team: Team::Other,
+        progress: 0,
+    };
+    input.control_points[2] = ControlPointState {
+        owner: Team::Blue,
+        capping_team: Team::Red,
+        progress: 123,
+    };
+    input.cart_progress = 200;
+
+    let unpacked = ObjectiveState::unpack(input.pack(&world), &world);
+    assert_eq!(input, unpacked);
+
+    input.cart_position = Some(VectorXY {
+        x: 100.0,
+        y: -5000.0,
+    });
+    let unpacked = ObjectiveState::unpack(input.pack(&world), &world);
+    let position = unpacked.cart_position.unwrap();
+    assert!(f32::abs(100.0 - position.x) < 0.5);
+    assert!(f32::abs(-5000.0 - position.y) < 0.5);
+}
+
+#[test]
+fn test_capture_progress() {
+    let capture = Capture {
+        start_tick: 0,
+        start_progress: 0.0,
+        cap_time: 10.0 / capture_rate(2),
+        blocked_progress: None,
+    };
+    // 2 cappers capture 1.5 times as fast
+    assert!(f32::abs(capture.progress(100, 0.1) - 1.0) < 0.01);
+    assert!(f32::abs(capture.progress(50, 0.1) - 0.75) < 0.01);
+
+    let blocked = Capture {
+        blocked_progress: Some(0.4),
+        ..capture
+    };
+    assert_eq!(0.4, blocked.progress(100, 0.1));
+}
+
+#[test]
+fn test_blocked_capture() {
+    let mut tracker = ObjectiveTracker::default();
+    tracker.start_capture(0, 0, Team::Red, 10.0, 1);
+    tracker.state(50, 0.1);
+    tracker.captures[0].as_mut().unwrap().blocked_progress = Some(0.5);
+    assert_eq!(127, tracker.state(60, 0.1).control_points[0].progress);
+
+    // the same team continues from where the capture was blocked
+    tracker.start_capture(0, 100, Team::Red, 10.0, 1);
+    assert_eq!(190, tracker.state(125, 0.1).control_points[0].progress);
+
+    // the other team starts from scratch
+    tracker.captures[0].as_mut().unwrap().blocked_progress = Some(0.7);
+    tracker.state(130, 0.1);
+    tracker.start_capture(0, 200, Team::Blue, 10.0, 1);
+    assert_eq!(25, tracker.state(210, 0.1).control_points[0].progress);
+}
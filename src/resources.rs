@@ -0,0 +1,128 @@
+use crate::history::Timed;
+use serde::Serialize;
+use tf_demo_parser::demo::data::DemoTick;
+use tf_demo_parser::demo::parser::gamestateanalyser::{GameState, Player};
+
+/// Ammo types of the primary, secondary and metal reserves, used as index in the player's ammo
+/// array
+const AMMO_PRIMARY: usize = 1;
+const AMMO_SECONDARY: usize = 2;
+const AMMO_METAL: usize = 3;
+
+/// Ammo and metal for a single player slot
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ResourceEntry {
+    pub metal: u16,
+    /// `None` for weapons without a clip
+    pub primary_clip: Option<u16>,
+    pub primary_reserve: u16,
+    pub secondary_clip: Option<u16>,
+    pub secondary_reserve: u16,
+}
+
+impl ResourceEntry {
+    fn new(ammo: &[u16], weapons: &[WeaponAmmo]) -> Self {
+        let reserve = |index: usize| ammo.get(index).copied().unwrap_or_default();
+        // the weapon list isn't in slot order, so the weapon is found by the ammo type it uses,
+        // which also matches the clip with the reserve it reloads from
+        let clip = |ammo_type: usize| {
+            weapons
+                .iter()
+                .find(|weapon| weapon.ammo_type == Some(ammo_type))
+                .and_then(|weapon| weapon.clip)
+        };
+        ResourceEntry {
+            metal: reserve(AMMO_METAL),
+            primary_clip: clip(AMMO_PRIMARY),
+            primary_reserve: reserve(AMMO_PRIMARY),
+            secondary_clip: clip(AMMO_SECONDARY),
+            secondary_reserve: reserve(AMMO_SECONDARY),
+        }
+    }
+}
+
+/// The ammo type and clip of a single weapon
+#[derive(Debug, Clone, Copy)]
+struct WeaponAmmo {
+    /// `None` for weapons that don't use ammo
+    ammo_type: Option<usize>,
+    clip: Option<u16>,
+}
+
+impl WeaponAmmo {
+    fn for_player(game_state: &GameState, player: &Player) -> Vec<Self> {
+        player
+            .weapons
+            .iter()
+            .filter_map(|handle| game_state.weapons.get(handle))
+            .map(|weapon| WeaponAmmo {
+                ammo_type: usize::try_from(weapon.ammo_type).ok(),
+                clip: weapon.clip,
+            })
+            .collect()
+    }
+}
+
+/// The ammo and metal for all player slots at a tick
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceSample {
+    pub tick: DemoTick,
+    pub players: Vec<ResourceEntry>,
+}
+
+/// Keep a low frequency series of ammo and metal samples.
+///
+/// The ammo counts are only networked to the player themselves, so outside of POV demos most
+/// entries will stay empty.
+#[derive(Debug, Default)]
+pub struct ResourceTracker {
+    pub samples: Vec<ResourceSample>,
+}
+
+impl ResourceTracker {
+    pub fn sample(&mut self, tick: DemoTick, game_state: &GameState) {
+        self.samples.push(ResourceSample {
+            tick,
+            players: game_state
+                .players
+                .iter()
+                .map(|player| {
+                    ResourceEntry::new(&player.ammo, &WeaponAmmo::for_player(game_state, player))
+                })
+                .collect(),
+        });
+    }
+}
+
+impl Timed for ResourceSample {
+    fn tick(&self) -> DemoTick {
+        self.tick
+    }
+}
+
+#[test]
+fn test_resource_entry() {
+    let weapon = |ammo_type: Option<usize>, clip: Option<u16>| WeaponAmmo { ammo_type, clip };
+    // weapons out of slot order: melee, secondary, primary
+    let weapons = [
+        weapon(None, None),
+        weapon(Some(AMMO_SECONDARY), Some(8)),
+        weapon(Some(AMMO_PRIMARY), Some(4)),
+    ];
+    let entry = ResourceEntry::new(&[0, 16, 24, 0], &weapons);
+    assert_eq!(Some(4), entry.primary_clip);
+    assert_eq!(16, entry.primary_reserve);
+    assert_eq!(Some(8), entry.secondary_clip);
+    assert_eq!(24, entry.secondary_reserve);
+    assert_eq!(0, entry.metal);
+
+    // a weapon without a clip and metal for an engineer
+    let weapons = [
+        weapon(Some(AMMO_PRIMARY), None),
+        weapon(Some(AMMO_METAL), None),
+    ];
+    let entry = ResourceEntry::new(&[0, 32, 0, 200], &weapons);
+    assert_eq!(None, entry.primary_clip);
+    assert_eq!(None, entry.secondary_clip);
+    assert_eq!(200, entry.metal);
+}
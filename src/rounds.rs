@@ -0,0 +1,396 @@
+use crate::history::{entry_at, Timed};
+use serde::Serialize;
+use tf_demo_parser::demo::data::DemoTick;
+use tf_demo_parser::demo::gamevent::GameEvent;
+use tf_demo_parser::demo::parser::gamestateanalyser::Team;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentKind {
+    /// Waiting for players and anything else before the first round starts
+    Pregame,
+    /// Pre-round freeze time
+    Preround,
+    Setup,
+    Round,
+    SuddenDeath,
+    /// Time between a round ending and the next one starting
+    Humiliation,
+    /// Time after the match ended
+    Postgame,
+}
+
+/// A stretch of the demo with the same game phase
+#[derive(Debug, Clone, Serialize)]
+pub struct Segment {
+    pub kind: SegmentKind,
+    pub start_tick: u32,
+    pub end_tick: u32,
+    /// Round number, starting from 1, `None` outside of rounds
+    pub round: Option<u32>,
+    /// Winning team of the round this segment is part of, if the round was won by a team
+    pub winner: Option<Team>,
+}
+
+/// The game events relevant for splitting the demo into segments
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoundEvent {
+    WaitingBegins,
+    RoundStart,
+    RoundActive,
+    SetupFinished,
+    Stalemate,
+    RoundWin {
+        team: Team,
+        /// Whether this ends the full round or only a stage of a multi stage map
+        full_round: bool,
+    },
+    GameOver,
+}
+
+impl RoundEvent {
+    pub fn from_event(event: &GameEvent) -> Option<RoundEvent> {
+        match event {
+            GameEvent::TeamPlayWaitingBegins(_) => Some(RoundEvent::WaitingBegins),
+            GameEvent::TeamPlayRoundStart(_) => Some(RoundEvent::RoundStart),
+            GameEvent::TeamPlayRoundActive(_) => Some(RoundEvent::RoundActive),
+            GameEvent::TeamPlaySetupFinished(_) => Some(RoundEvent::SetupFinished),
+            GameEvent::TeamPlayRoundStalemate(_) => Some(RoundEvent::Stalemate),
+            GameEvent::TeamPlayRoundWin(event) => Some(RoundEvent::RoundWin {
+                team: Team::new(event.team as u16),
+                full_round: event.full_round != 0,
+            }),
+            GameEvent::TeamPlayGameOver(_) | GameEvent::TfGameOver(_) => Some(RoundEvent::GameOver),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Timeline {
+    segments: Vec<Segment>,
+    current: Segment,
+    round: u32,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Timeline {
+            segments: Vec::new(),
+            current: Segment {
+                kind: SegmentKind::Pregame,
+                start_tick: 0,
+                end_tick: 0,
+                round: None,
+                winner: None,
+            },
+            round: 0,
+        }
+    }
+}
+
+impl Timeline {
+    pub fn handle_event(&mut self, tick: DemoTick, event: RoundEvent) {
+        let tick = u32::from(tick);
+        match event {
+            RoundEvent::WaitingBegins => self.start(tick, SegmentKind::Pregame, None),
+            RoundEvent::RoundStart => {
+                self.round += 1;
+                self.start(tick, SegmentKind::Preround, Some(self.round));
+            }
+            RoundEvent::RoundActive => self.start(tick, SegmentKind::Round, Some(self.round)),
+            RoundEvent::SetupFinished => {
+                // we only know that the round had setup time once it's finished
+                if self.current.kind == SegmentKind::Round {
+                    self.current.kind = SegmentKind::Setup;
+                }
+                self.start(tick, SegmentKind::Round, Some(self.round));
+            }
+            RoundEvent::Stalemate => self.start(tick, SegmentKind::SuddenDeath, Some(self.round)),
+            RoundEvent::RoundWin { team, .. } => {
+                let winner = matches!(team, Team::Red | Team::Blue).then_some(team);
+                let round = Some(self.round);
+                for segment in self
+                    .segments
+                    .iter_mut()
+                    .chain(std::iter::once(&mut self.current))
+                    .filter(|segment| segment.round == round)
+                {
+                    segment.winner = winner;
+                }
+                self.start(tick, SegmentKind::Humiliation, round);
+                self.current.winner = winner;
+            }
+            RoundEvent::GameOver => self.start(tick, SegmentKind::Postgame, None),
+        }
+    }
+
+    fn start(&mut self, tick: u32, kind: SegmentKind, round: Option<u32>) {
+        if self.current.kind == kind && self.current.round == round {
+            return;
+        }
+        let next = Segment {
+            kind,
+            start_tick: tick,
+            end_tick: tick,
+            round,
+            winner: None,
+        };
+        let mut previous = std::mem::replace(&mut self.current, next);
+        previous.end_tick = tick;
+        if previous.end_tick > previous.start_tick {
+            self.segments.push(previous);
+        }
+    }
+
+    pub fn finish(mut self, end_tick: u32) -> Vec<Segment> {
+        self.current.end_tick = end_tick;
+        if self.current.end_tick > self.current.start_tick {
+            self.segments.push(self.current);
+        }
+        self.segments
+    }
+}
+
+/// The team scores starting at a tick
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Score {
+    pub tick: DemoTick,
+    pub red: u16,
+    pub blue: u16,
+}
+
+impl Timed for Score {
+    fn tick(&self) -> DemoTick {
+        self.tick
+    }
+}
+
+/// Keep track of the team scores.
+///
+/// The scores from the team entities take priority, that way demos that start in the middle of
+/// a match still get the rounds played before the recording started. Round wins are only counted
+/// when no team entity scores are available.
+#[derive(Debug, Default)]
+pub struct ScoreTracker {
+    scores: Vec<Score>,
+    /// Whether we got any scores from the team entities
+    from_entities: bool,
+}
+
+impl ScoreTracker {
+    pub fn handle_event(&mut self, tick: DemoTick, event: RoundEvent) {
+        if self.from_entities {
+            return;
+        }
+        let last = self.last();
+        match event {
+            // the scores get reset when the match goes live after waiting for players
+            RoundEvent::WaitingBegins if last.red > 0 || last.blue > 0 => {
+                self.scores.push(Score {
+                    tick,
+                    red: 0,
+                    blue: 0,
+                });
+            }
+            RoundEvent::RoundWin {
+                team,
+                full_round: true,
+            } => {
+                let score = match team {
+                    Team::Red => Score {
+                        tick,
+                        red: last.red + 1,
+                        ..last
+                    },
+                    Team::Blue => Score {
+                        tick,
+                        blue: last.blue + 1,
+                        ..last
+                    },
+                    _ => return,
+                };
+                self.scores.push(score);
+            }
+            _ => {}
+        }
+    }
+
+    /// Update the scores from the score props of the team entities
+    pub fn handle_team_scores(&mut self, tick: DemoTick, red: Option<u16>, blue: Option<u16>) {
+        let (Some(red), Some(blue)) = (red, blue) else {
+            return;
+        };
+        self.from_entities = true;
+        let last = self.last();
+        if last.red != red || last.blue != blue {
+            self.scores.push(Score { tick, red, blue });
+        }
+    }
+
+    fn last(&self) -> Score {
+        self.scores.last().copied().unwrap_or_default()
+    }
+
+    pub fn finish(self) -> Vec<Score> {
+        self.scores
+    }
+}
+
+/// Get the scores at a tick from the list of score changes
+pub fn score_at(scores: &[Score], tick: DemoTick) -> Score {
+    entry_at(scores, tick).copied().unwrap_or_default()
+}
+
+#[test]
+fn test_scores() {
+    let events = [
+        (
+            100,
+            RoundEvent::RoundWin {
+                team: Team::Red,
+                full_round: true,
+            },
+        ),
+        (200, RoundEvent::WaitingBegins),
+        (
+            300,
+            RoundEvent::RoundWin {
+                team: Team::Blue,
+                full_round: true,
+            },
+        ),
+        (
+            400,
+            RoundEvent::RoundWin {
+                team: Team::Red,
+                full_round: false,
+            },
+        ),
+        (
+            500,
+            RoundEvent::RoundWin {
+                team: Team::Red,
+                full_round: true,
+            },
+        ),
+    ];
+
+    let mut tracker = ScoreTracker::default();
+    for (tick, event) in events {
+        tracker.handle_event(DemoTick::from(tick), event);
+    }
+    let scores = tracker.finish();
+
+    let score = |tick: u32| {
+        let score = score_at(&scores, DemoTick::from(tick));
+        (score.red, score.blue)
+    };
+    assert_eq!((0, 0), score(0));
+    assert_eq!((1, 0), score(100));
+    assert_eq!((0, 0), score(200));
+    assert_eq!((0, 1), score(450));
+    assert_eq!((1, 1), score(500));
+}
+
+#[test]
+fn test_scores_from_entities() {
+    let mut tracker = ScoreTracker::default();
+    // recording started after a few rounds were played
+    tracker.handle_team_scores(DemoTick::from(10), Some(3), Some(2));
+    tracker.handle_event(
+        DemoTick::from(20),
+        RoundEvent::RoundWin {
+            team: Team::Red,
+            full_round: true,
+        },
+    );
+    tracker.handle_team_scores(DemoTick::from(21), Some(4), Some(2));
+    tracker.handle_team_scores(DemoTick::from(30), Some(4), Some(2));
+    let scores = tracker.finish();
+
+    assert_eq!(2, scores.len());
+    let score = |tick: u32| {
+        let score = score_at(&scores, DemoTick::from(tick));
+        (score.red, score.blue)
+    };
+    assert_eq!((0, 0), score(0));
+    assert_eq!((3, 2), score(10));
+    assert_eq!((3, 2), score(20));
+    assert_eq!((4, 2), score(21));
+}
+
+#[test]
+fn test_timeline() {
+    let events = [
+        (10, RoundEvent::WaitingBegins),
+        (100, RoundEvent::RoundStart),
+        (110, RoundEvent::RoundActive),
+        (200, RoundEvent::SetupFinished),
+        (
+            500,
+            RoundEvent::RoundWin {
+                team: Team::Red,
+                full_round: true,
+            },
+        ),
+        (600, RoundEvent::RoundStart),
+        (610, RoundEvent::RoundActive),
+        (700, RoundEvent::Stalemate),
+        (
+            800,
+            RoundEvent::RoundWin {
+                team: Team::Blue,
+                full_round: true,
+            },
+        ),
+        (900, RoundEvent::GameOver),
+    ];
+
+    let mut timeline = Timeline::default();
+    for (tick, event) in events {
+        timeline.handle_event(DemoTick::from(tick), event);
+    }
+    let segments = timeline.finish(1000);
+
+    let summary: Vec<_> = segments
+        .iter()
+        .map(|segment| {
+            (
+                segment.kind,
+                segment.start_tick,
+                segment.end_tick,
+                segment.round,
+                segment.winner,
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        vec![
+            (SegmentKind::Pregame, 0, 100, None, None),
+            (SegmentKind::Preround, 100, 110, Some(1), Some(Team::Red)),
+            (SegmentKind::Setup, 110, 200, Some(1), Some(Team::Red)),
+            (SegmentKind::Round, 200, 500, Some(1), Some(Team::Red)),
+            (SegmentKind::Humiliation, 500, 600, Some(1), Some(Team::Red)),
+            (SegmentKind::Preround, 600, 610, Some(2), Some(Team::Blue)),
+            (SegmentKind::Round, 610, 700, Some(2), Some(Team::Blue)),
+            (
+                SegmentKind::SuddenDeath,
+                700,
+                800,
+                Some(2),
+                Some(Team::Blue)
+            ),
+            (
+                SegmentKind::Humiliation,
+                800,
+                900,
+                Some(2),
+                Some(Team::Blue)
+            ),
+            (SegmentKind::Postgame, 900, 1000, None, None),
+        ],
+        summary
+    );
+}
@@ -0,0 +1,102 @@
+use crate::history::Timed;
+use crate::identity::SlotOccupancy;
+use crate::kill::KillRecord;
+use crate::stats::KillCredit;
+use serde::Serialize;
+use tf_demo_parser::demo::data::DemoTick;
+
+/// The scoreboard values for a single player slot
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ScoreboardEntry {
+    pub ping: u16,
+    pub score: u32,
+    pub connected: bool,
+    pub alive: bool,
+    /// Kills, deaths and assists counted from the death events
+    pub kills: u16,
+    pub deaths: u16,
+    pub assists: u16,
+}
+
+/// The scoreboard for all player slots at a tick
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoreboardSample {
+    pub tick: DemoTick,
+    pub players: Vec<ScoreboardEntry>,
+}
+
+/// Keep a low frequency series of scoreboard samples.
+///
+/// Ping, score and connection state come from the player resource, the player resource doesn't
+/// have kills, deaths and assists so those are counted from the death events.
+#[derive(Debug, Default)]
+pub struct ScoreboardTracker {
+    current: Vec<ScoreboardEntry>,
+    pub samples: Vec<ScoreboardSample>,
+}
+
+impl ScoreboardTracker {
+    fn entry(&mut self, slot: usize) -> &mut ScoreboardEntry {
+        if self.current.len() <= slot {
+            self.current.resize(slot + 1, ScoreboardEntry::default());
+        }
+        &mut self.current[slot]
+    }
+
+    pub fn handle_kill(&mut self, kill: &KillRecord, occupancies: &[SlotOccupancy]) {
+        let Some(credit) = KillCredit::new(kill, occupancies) else {
+            return;
+        };
+        let slot = |occupancy: Option<usize>| occupancy.map(|index| occupancies[index].slot);
+        if let Some(victim) = slot(credit.victim) {
+            self.entry(victim).deaths += 1;
+        }
+        if let Some(attacker) = slot(credit.attacker) {
+            self.entry(attacker).kills += 1;
+        }
+        if let Some(assister) = slot(credit.assister) {
+            self.entry(assister).assists += 1;
+        }
+    }
+
+    pub fn update(&mut self, slot: usize, ping: u16, score: u32, connected: bool, alive: bool) {
+        let entry = self.entry(slot);
+        entry.ping = ping;
+        entry.score = score;
+        entry.connected = connected;
+        entry.alive = alive;
+    }
+
+    pub fn sample(&mut self, tick: DemoTick) {
+        self.samples.push(ScoreboardSample {
+            tick,
+            players: self.current.clone(),
+        });
+    }
+}
+
+impl Timed for ScoreboardSample {
+    fn tick(&self) -> DemoTick {
+        self.tick
+    }
+}
+
+#[test]
+fn test_scoreboard_samples() {
+    use crate::history::entry_at;
+
+    let mut tracker = ScoreboardTracker::default();
+    tracker.update(1, 50, 2, true, true);
+    tracker.sample(DemoTick::from(0u32));
+    tracker.update(1, 60, 3, true, false);
+    tracker.sample(DemoTick::from(66u32));
+
+    let ping_at = |tick: u32| {
+        entry_at(&tracker.samples, DemoTick::from(tick)).map(|sample| sample.players[1].ping)
+    };
+    assert_eq!(Some(50), ping_at(0));
+    assert_eq!(Some(50), ping_at(65));
+    assert_eq!(Some(60), ping_at(100));
+    assert_eq!(3, tracker.samples[1].players[1].score);
+    assert_eq!(2, tracker.samples[0].players.len());
+}
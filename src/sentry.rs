@@ -0,0 +1,116 @@
+use crate::columns::BuildingColumns;
+use crate::history::{entry_at, Timed};
+use crate::state::player_slot;
+use serde::Serialize;
+use tf_demo_parser::demo::data::DemoTick;
+use tf_demo_parser::demo::parser::gamestateanalyser::{Building, GameState, Sentry, UserId};
+
+/// The target, ammo and wrangled state of a sentry
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SentryState {
+    /// Player slot of the player the sentry is aiming at
+    pub target: Option<usize>,
+    pub shells: u16,
+    pub rockets: u16,
+    pub wrangled: bool,
+}
+
+impl SentryState {
+    fn new(sentry: &Sentry, game_state: &GameState) -> Self {
+        let target = sentry.auto_aim_target;
+        SentryState {
+            target: (target != UserId::from(0u16))
+                .then(|| player_slot(game_state, target))
+                .flatten(),
+            shells: sentry.shells,
+            rockets: sentry.rockets,
+            wrangled: sentry.player_controlled,
+        }
+    }
+}
+
+/// The state of a sentry starting at a specific tick
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SentryChange {
+    pub tick: DemoTick,
+    /// `None` once the building column no longer holds a sentry
+    pub state: Option<SentryState>,
+}
+
+impl Timed for SentryChange {
+    fn tick(&self) -> DemoTick {
+        self.tick
+    }
+}
+
+/// Record the target and ammo of every sentry whenever it changes, indexed by building column.
+///
+/// A column that stops holding a sentry gets an empty record, so the history of a removed sentry
+/// doesn't carry on into the next building that gets the column.
+#[derive(Debug, Default)]
+pub struct SentryTracker {
+    pub changes: Vec<Vec<SentryChange>>,
+}
+
+impl SentryTracker {
+    pub fn update(&mut self, game_state: &GameState, columns: &BuildingColumns) {
+        for index in 0..columns.column_count() {
+            let building = columns
+                .building(index)
+                .and_then(|id| game_state.buildings.get(&id));
+            let state = match building {
+                Some(Building::Sentry(sentry)) => Some(SentryState::new(sentry, game_state)),
+                _ => None,
+            };
+            self.record(index, game_state.tick, state);
+        }
+    }
+
+    fn record(&mut self, index: usize, tick: DemoTick, state: Option<SentryState>) {
+        if self.changes.len() <= index {
+            if state.is_none() {
+                return;
+            }
+            self.changes.resize_with(index + 1, Vec::new);
+        }
+
+        let history = &mut self.changes[index];
+        let last = history.last().and_then(|last| last.state);
+        if last != state {
+            history.push(SentryChange { tick, state });
+        }
+    }
+}
+
+/// Get the sentry state at a specific tick from its change history
+pub fn sentry_at(history: &[SentryChange], tick: DemoTick) -> Option<&SentryState> {
+    entry_at(history, tick).and_then(|change| change.state.as_ref())
+}
+
+#[test]
+fn test_sentry_column_reuse() {
+    let sentry = SentryState {
+        target: Some(3),
+        shells: 150,
+        rockets: 20,
+        wrangled: false,
+    };
+    let mut tracker = SentryTracker::default();
+    // a dispenser in the column doesn't start a history
+    tracker.record(1, DemoTick::from(5u32), None);
+    assert!(tracker.changes.is_empty());
+
+    tracker.record(0, DemoTick::from(10u32), Some(sentry));
+    tracker.record(0, DemoTick::from(11u32), Some(sentry));
+    // the sentry gets destroyed
+    tracker.record(0, DemoTick::from(20u32), None);
+    // and a dispenser is built in the same column
+    tracker.record(0, DemoTick::from(30u32), None);
+
+    let history = &tracker.changes[0];
+    assert_eq!(2, history.len());
+    assert_eq!(None, sentry_at(history, DemoTick::from(5u32)));
+    assert_eq!(Some(&sentry), sentry_at(history, DemoTick::from(15u32)));
+    assert_eq!(None, sentry_at(history, DemoTick::from(20u32)));
+    assert_eq!(None, sentry_at(history, DemoTick::from(35u32)));
+}
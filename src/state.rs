@@ -1,11 +1,25 @@
+use crate::columns::BuildingColumns;
+use crate::damage::{DamageRecord, DeflectRecord, HealRecord};
+use crate::identity::{NameChange, PlayerIdentity, SlotOccupancy};
+use crate::kill::{AirborneTracker, KillRecord};
+use crate::loadout::LoadoutTracker;
+use crate::objective::ObjectiveTracker;
+#[cfg(feature = "resources")]
+use crate::resources::ResourceTracker;
+use crate::rounds::{RoundEvent, Score, ScoreTracker, Segment, Timeline};
+use crate::scoreboard::ScoreboardTracker;
+use crate::sentry::SentryTracker;
+use crate::sticky::StickyTracker;
+use crate::teleporter::TeleporterTracker;
 use serde::{Deserialize, Serialize};
 use tf_demo_parser::demo::data::game_state::{Projectile, ProjectileType};
 use tf_demo_parser::demo::data::DemoTick;
 use tf_demo_parser::demo::gamevent::GameEvent;
 use tf_demo_parser::demo::header::Header;
+use tf_demo_parser::demo::message::packetentities::EntityId;
 use tf_demo_parser::demo::parser::analyser::UserInfo;
 use tf_demo_parser::demo::parser::gamestateanalyser::{
-    Building, Class, Dispenser, GameState, Kill, PlayerState as PlayerAliveState, Sentry, Team,
+    Building, Class, Dispenser, GameState, PlayerState as PlayerAliveState, Sentry, Team,
     Teleporter, UserId, World,
 };
 use tf_demo_parser::demo::vector::VectorXY;
@@ -34,12 +48,39 @@ pub struct ParsedDemo {
     pub players: Vec<Vec<u8>>,
     pub buildings: Vec<Vec<u8>>,
     pub projectiles: Vec<Vec<u8>>,
-    pub kills: Vec<Kill>,
+    pub kills: Vec<KillRecord>,
+    pub damage: Vec<DamageRecord>,
+    pub healing: Vec<HealRecord>,
+    pub deflects: Vec<DeflectRecord>,
     pub events: Vec<SearchableEvent>,
     pub header: Header,
-    pub player_info: Vec<UserInfo>,
+    /// Player info indexed by player slot, `None` for slots we haven't seen info for (yet)
+    pub player_info: Vec<Option<UserInfo>>,
     pub max_building_count: usize,
+    building_columns: BuildingColumns,
     pub max_projectile_count: usize,
+    pub occupancies: Vec<SlotOccupancy>,
+    /// Index into `occupancies` for the user currently in each player slot
+    active_occupancies: Vec<Option<usize>>,
+    pub identities: Vec<PlayerIdentity>,
+    /// Name history indexed by player slot
+    pub name_changes: Vec<Vec<NameChange>>,
+    pub segments: Vec<Segment>,
+    pub scores: Vec<Score>,
+    score_tracker: ScoreTracker,
+    /// Packed `ObjectiveState` for every tick
+    pub objectives: Vec<u8>,
+    pub objective: ObjectiveTracker,
+    pub scoreboard: ScoreboardTracker,
+    pub loadouts: LoadoutTracker,
+    pub stickies: StickyTracker,
+    pub sentries: SentryTracker,
+    pub teleporters: TeleporterTracker,
+    #[cfg(feature = "resources")]
+    pub resources: ResourceTracker,
+    /// Number of events from the game state we already handled
+    handled_events: usize,
+    airborne: Vec<AirborneTracker>,
 }
 
 impl ParsedDemo {
@@ -51,15 +92,54 @@ impl ParsedDemo {
             buildings: Vec::new(),
             projectiles: Vec::new(),
             kills: Vec::new(),
+            damage: Vec::new(),
+            healing: Vec::new(),
+            deflects: Vec::new(),
             player_info: Vec::new(),
             max_building_count: 0,
+            building_columns: BuildingColumns::default(),
             max_projectile_count: 0,
+            occupancies: Vec::new(),
+            active_occupancies: Vec::new(),
+            identities: Vec::new(),
+            name_changes: Vec::new(),
+            segments: Vec::new(),
+            scores: Vec::new(),
+            score_tracker: ScoreTracker::default(),
+            objectives: Vec::new(),
+            objective: ObjectiveTracker::default(),
+            scoreboard: ScoreboardTracker::default(),
+            loadouts: LoadoutTracker::default(),
+            stickies: StickyTracker::default(),
+            sentries: SentryTracker::default(),
+            teleporters: TeleporterTracker::default(),
+            #[cfg(feature = "resources")]
+            resources: ResourceTracker::default(),
+            handled_events: 0,
+            airborne: Vec::new(),
             events: Vec::new(),
             header,
         }
     }
 
     pub fn push_state(&mut self, game_state: &GameState) {
+        self.track_occupancies(game_state);
+        self.track_names(game_state);
+        self.track_airborne(game_state);
+        self.objective.update(game_state);
+        self.track_team_scores(game_state);
+        self.loadouts.update(game_state);
+        self.stickies.update(game_state);
+        let buildings: Vec<EntityId> = game_state.buildings.keys().copied().collect();
+        self.building_columns.update(&buildings);
+        self.sentries.update(game_state, &self.building_columns);
+        self.teleporters.update(game_state, &self.building_columns);
+        self.handle_new_events(game_state);
+
+        let interval_per_tick = self.header.duration / (self.header.ticks as f32);
+        // sample the scoreboard and resources about once per second
+        let sample_interval = ((1.0 / interval_per_tick).round() as usize).max(1);
+
         if let Some(world) = game_state.world.as_ref() {
             for _tick in u32::from(self.last_tick)..u32::from(game_state.tick) {
                 for (index, player) in game_state.players.iter().enumerate() {
@@ -74,6 +154,10 @@ impl ParsedDemo {
                         team: player.team,
                         class: player.class,
                         charge: player.charge,
+                        conditions: ConditionFlags::new(&player.conditions),
+                        disguise_team: player.disguise_team,
+                        disguise_class: player.disguise_class,
+                        max_health: player.max_health,
                     };
 
                     if self.players.get(index).is_none() {
@@ -85,24 +169,30 @@ impl ParsedDemo {
                         self.players.push(new_player);
                     };
 
-                    if let (None, Some(info)) = (self.player_info.get(index), player.info.as_ref())
-                    {
-                        self.player_info.push(info.clone());
+                    if self.player_info.len() <= index {
+                        self.player_info.resize(index + 1, None);
+                    }
+                    if let (None, Some(info)) = (&self.player_info[index], player.info.as_ref()) {
+                        self.player_info[index] = Some(info.clone());
                     }
 
                     let parsed_player = &mut self.players[index];
                     parsed_player.extend_from_slice(&state.pack(world));
                 }
 
-                self.max_building_count = self.max_building_count.max(game_state.buildings.len());
-                for (index, building) in game_state.buildings.values().enumerate() {
+                self.max_building_count = self.building_columns.column_count();
+                for (id, building) in game_state.buildings.iter() {
+                    let Some(index) = self.building_columns.column(*id) else {
+                        continue;
+                    };
                     let state = BuildingState::new(building);
 
-                    if self.buildings.get(index).is_none() {
-                        let new_building = Vec::with_capacity(
-                            self.header.ticks as usize * BuildingState::PACKET_SIZE,
-                        );
-                        self.buildings.push(new_building);
+                    if self.buildings.len() <= index {
+                        self.buildings.resize_with(index + 1, || {
+                            Vec::with_capacity(
+                                self.header.ticks as usize * BuildingState::PACKET_SIZE,
+                            )
+                        });
                     };
 
                     let parsed_building = &mut self.buildings[index];
@@ -114,7 +204,8 @@ impl ParsedDemo {
                 self.max_projectile_count =
                     self.max_projectile_count.max(game_state.projectiles.len());
                 for (index, projectile) in game_state.projectiles.values().enumerate() {
-                    let state = ProjectileState::new(projectile);
+                    let owner = projectile_owner(game_state, projectile);
+                    let state = ProjectileState::new(projectile, owner);
 
                     if self.projectiles.get(index).is_none() {
                         let new_projectile = Vec::with_capacity(
@@ -128,12 +219,156 @@ impl ParsedDemo {
 
                     parsed_projectiles.extend_from_slice(&state.pack(world));
                 }
+
+                let objective = self.objective.state(self.tick, interval_per_tick);
+                self.objectives.extend_from_slice(&objective.pack(world));
+
+                if self.tick % sample_interval == 0 {
+                    for (slot, player) in game_state.players.iter().enumerate() {
+                        let alive = player.state == PlayerAliveState::Alive;
+                        self.scoreboard.update(
+                            slot,
+                            player.ping,
+                            player.score,
+                            player.connected,
+                            alive,
+                        );
+                    }
+                    let tick = DemoTick::from(self.tick as u32);
+                    self.scoreboard.sample(tick);
+                    #[cfg(feature = "resources")]
+                    self.resources.sample(tick, game_state);
+                }
+
                 self.tick += 1;
             }
             self.last_tick = game_state.tick;
         }
     }
 
+    /// Keep track of which user is in which player slot, a reconnecting player can end up in
+    /// a different slot, or a slot can be re-used by a different player
+    fn track_occupancies(&mut self, game_state: &GameState) {
+        for (slot, player) in game_state.players.iter().enumerate() {
+            let Some(info) = player.info.as_ref() else {
+                continue;
+            };
+            if self.active_occupancies.len() <= slot {
+                self.active_occupancies.resize(slot + 1, None);
+            }
+
+            if let Some(active) = self.active_occupancies[slot] {
+                let occupancy = &mut self.occupancies[active];
+                if occupancy.user_id == info.user_id {
+                    continue;
+                }
+                occupancy.disconnect_tick.get_or_insert(game_state.tick);
+            }
+
+            self.active_occupancies[slot] = Some(self.occupancies.len());
+            self.occupancies.push(SlotOccupancy {
+                slot,
+                user_id: info.user_id,
+                steam_id: info.steam_id.clone(),
+                name: info.name.clone(),
+                connect_tick: game_state.tick,
+                disconnect_tick: None,
+            });
+        }
+    }
+
+    fn track_team_scores(&mut self, game_state: &GameState) {
+        let score = |team: Team| {
+            game_state
+                .teams
+                .iter()
+                .find(|state| state.team == team)
+                .map(|state| state.score)
+        };
+        self.score_tracker
+            .handle_team_scores(game_state.tick, score(Team::Red), score(Team::Blue));
+    }
+
+    fn track_names(&mut self, game_state: &GameState) {
+        for (slot, player) in game_state.players.iter().enumerate() {
+            let Some(info) = player.info.as_ref() else {
+                continue;
+            };
+            if self.name_changes.len() <= slot {
+                self.name_changes.resize_with(slot + 1, Vec::new);
+            }
+
+            let history = &mut self.name_changes[slot];
+            if history.last().is_none_or(|last| last.name != info.name) {
+                history.push(NameChange {
+                    tick: game_state.tick,
+                    name: info.name.clone(),
+                });
+            }
+        }
+    }
+
+    fn track_airborne(&mut self, game_state: &GameState) {
+        if self.airborne.len() < game_state.players.len() {
+            self.airborne
+                .resize(game_state.players.len(), AirborneTracker::default());
+        }
+        for (tracker, player) in self.airborne.iter_mut().zip(game_state.players.iter()) {
+            tracker.update(u32::from(game_state.tick), player.position.z);
+        }
+    }
+
+    fn handle_new_events(&mut self, game_state: &GameState) {
+        for (tick, event) in &game_state.events[self.handled_events..] {
+            self.objective.handle_event(self.tick, event);
+            if let Some(round_event) = RoundEvent::from_event(event) {
+                self.score_tracker.handle_event(*tick, round_event);
+            }
+            self.handle_event(*tick, event, game_state);
+        }
+        self.handled_events = game_state.events.len();
+    }
+
+    fn handle_event(&mut self, tick: DemoTick, event: &GameEvent, game_state: &GameState) {
+        match event {
+            GameEvent::PlayerDeath(death) => {
+                let attacker_slot = player_slot(game_state, UserId::from(death.attacker));
+                let victim_slot = player_slot(game_state, UserId::from(death.user_id));
+                let victim_airborne = victim_slot
+                    .and_then(|slot| self.airborne.get(slot))
+                    .is_some_and(AirborneTracker::is_airborne);
+
+                let mut kill = KillRecord::new(tick, death, victim_airborne);
+                if let Some(slot) = attacker_slot {
+                    let attacker = &game_state.players[slot];
+                    kill.attacker_slot = Some(slot);
+                    kill.attacker_position = Some(attacker.position);
+                    kill.attacker_team = attacker.team;
+                    kill.attacker_class = attacker.class;
+                }
+                if let Some(slot) = victim_slot {
+                    let victim = &game_state.players[slot];
+                    kill.victim_slot = Some(slot);
+                    kill.victim_position = Some(victim.position);
+                    kill.victim_team = victim.team;
+                    kill.victim_class = victim.class;
+                }
+                self.scoreboard.handle_kill(&kill, &self.occupancies);
+                self.kills.push(kill);
+            }
+            GameEvent::PlayerHurt(hurt) => self.damage.push(DamageRecord::new(tick, hurt)),
+            GameEvent::PlayerHealed(healed) => self.healing.push(HealRecord::new(tick, healed)),
+            GameEvent::ObjectDeflected(deflected) => {
+                let team = player_slot(game_state, UserId::from(deflected.user_id))
+                    .map(|slot| game_state.players[slot].team)
+                    .unwrap_or_default();
+                self.deflects
+                    .push(DeflectRecord::new(tick, deflected, team));
+            }
+            _ => {}
+        }
+    }
+
     pub fn finish(&mut self, state: &GameState) {
         for parsed_building in self.buildings.iter_mut() {
             parsed_building.resize(self.tick * BuildingState::PACKET_SIZE, 0);
@@ -147,6 +382,32 @@ impl ParsedDemo {
             .iter()
             .flat_map(|(tick, event)| SearchableEvent::from_event(*tick, event))
             .collect();
+
+        for (tick, event) in state.events.iter() {
+            if let GameEvent::PlayerDisconnect(event) = event {
+                let user_id = UserId::from(event.user_id);
+                if let Some(occupancy) = self
+                    .occupancies
+                    .iter_mut()
+                    .find(|occupancy| occupancy.user_id == user_id)
+                {
+                    occupancy.disconnect_tick = Some(match occupancy.disconnect_tick {
+                        Some(disconnect_tick) => disconnect_tick.min(*tick),
+                        None => *tick,
+                    });
+                }
+            }
+        }
+        self.identities = PlayerIdentity::group(&self.occupancies);
+
+        let mut timeline = Timeline::default();
+        for (tick, event) in state.events.iter() {
+            if let Some(event) = RoundEvent::from_event(event) {
+                timeline.handle_event(*tick, event);
+            }
+        }
+        self.segments = timeline.finish(self.tick as u32);
+        self.scores = std::mem::take(&mut self.score_tracker).finish();
     }
 
     pub fn size(&self) -> usize {
@@ -156,18 +417,113 @@ impl ParsedDemo {
     }
 }
 
+/// Find the slot of the player with the given user id
+pub fn player_slot(game_state: &GameState, user_id: UserId) -> Option<usize> {
+    game_state.players.iter().position(|player| {
+        player
+            .info
+            .as_ref()
+            .is_some_and(|info| info.user_id == user_id)
+    })
+}
+
+/// Find the slot of the player that fired a projectile from the weapon that launched it
+pub fn projectile_owner(game_state: &GameState, projectile: &Projectile) -> Option<usize> {
+    let owner = game_state.weapons.get(&projectile.launcher)?.owner;
+    game_state.players.iter().position(|player| {
+        player
+            .info
+            .as_ref()
+            .is_some_and(|info| info.entity_id == owner)
+    })
+}
+
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct PlayerState {
-    position: VectorXY,
-    angle: Angle,
-    health: u16,
-    team: Team,
-    class: Class,
-    charge: u8,
+    pub position: VectorXY,
+    pub angle: Angle,
+    pub health: u16,
+    pub team: Team,
+    pub class: Class,
+    pub charge: u8,
+    pub conditions: ConditionFlags,
+    /// Only meaningful while the `DISGUISED` condition is set
+    pub disguise_team: Team,
+    pub disguise_class: Class,
+    pub max_health: u16,
+}
+
+const COND_DISGUISED: usize = 3;
+const COND_STEALTHED: usize = 4;
+const COND_INVULNERABLE: usize = 5;
+const COND_CRITBOOSTED: usize = 11;
+const COND_OFFENSEBUFF: usize = 16;
+const COND_BURNING: usize = 22;
+const COND_URINE: usize = 24;
+const COND_BLEEDING: usize = 25;
+const COND_DEFENSEBUFF: usize = 26;
+const COND_REGENONDAMAGEBUFF: usize = 29;
+
+/// The player conditions relevant for the viewer, as exposed to js
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConditionFlags(pub u8);
+
+impl ConditionFlags {
+    pub const UBERCHARGED: u8 = 1 << 0;
+    pub const KRITZKRIEGED: u8 = 1 << 1;
+    pub const CLOAKED: u8 = 1 << 2;
+    pub const DISGUISED: u8 = 1 << 3;
+    pub const BURNING: u8 = 1 << 4;
+    pub const BLEEDING: u8 = 1 << 5;
+    pub const JARATED: u8 = 1 << 6;
+    /// Any of the soldier banners
+    pub const BUFFED: u8 = 1 << 7;
+
+    /// Convert the condition bitfield from the player entity
+    pub fn new(conditions: &[u8]) -> Self {
+        let has = |cond: usize| {
+            conditions
+                .get(cond / 8)
+                .is_some_and(|byte| byte & (1 << (cond % 8)) != 0)
+        };
+
+        let condition_map = [
+            (COND_INVULNERABLE, Self::UBERCHARGED),
+            (COND_CRITBOOSTED, Self::KRITZKRIEGED),
+            (COND_STEALTHED, Self::CLOAKED),
+            (COND_DISGUISED, Self::DISGUISED),
+            (COND_BURNING, Self::BURNING),
+            (COND_BLEEDING, Self::BLEEDING),
+            (COND_URINE, Self::JARATED),
+            (COND_OFFENSEBUFF, Self::BUFFED),
+            (COND_DEFENSEBUFF, Self::BUFFED),
+            (COND_REGENONDAMAGEBUFF, Self::BUFFED),
+        ];
+
+        let mut flags = 0;
+        for (cond, flag) in condition_map {
+            if has(cond) {
+                flags |= flag;
+            }
+        }
+        ConditionFlags(flags)
+    }
+
+    #[allow(dead_code)]
+    pub fn contains(&self, flag: u8) -> bool {
+        self.0 & flag == flag
+    }
 }
 
 impl PlayerState {
-    const PACKET_SIZE: usize = 8;
+    /// Version of the packet layout, bumped whenever the layout changes
+    ///
+    /// - 1: 2 bits team, 4 bits class, 10 bits health, 8 bytes
+    /// - 2: condition flags appended, 9 bytes
+    /// - 3: disguise team and class appended, 10 bytes
+    /// - 4: full 16 bit health, separate team and class byte, max health appended, 13 bytes
+    pub const PACKET_VERSION: u8 = 4;
+    pub const PACKET_SIZE: usize = 13;
 
     pub fn pack(&self, world: &World) -> [u8; Self::PACKET_SIZE] {
         // for the purpose of viewing the demo in the browser we dont really need high accuracy for
@@ -180,27 +536,35 @@ impl PlayerState {
 
         let x = pack_f32(self.position.x, world.boundary_min.x, world.boundary_max.x).to_le_bytes();
         let y = pack_f32(self.position.y, world.boundary_min.y, world.boundary_max.y).to_le_bytes();
+        // health gets the full 16 bits, overheal on custom servers or mvm giants can go well
+        // above what fits next to the team and class
+        let health = self.health.to_le_bytes();
+        let max_health = self.max_health.to_le_bytes();
         // 2 bits for team
         // 4 bits for class
-        // 10 bits for health
-        let team_class_health =
-            ((self.team as u16) << 14) + ((self.class as u16) << 10) + self.health;
-        let combined_bytes = team_class_health.to_le_bytes();
+        let team_class = ((self.team as u8) << 4) + (self.class as u8);
+        // 2 bits for disguise team
+        // 4 bits for disguise class
+        let disguise = ((self.disguise_team as u8) << 4) + (self.disguise_class as u8);
 
         [
             x[0],
             x[1],
             y[0],
             y[1],
-            combined_bytes[0],
-            combined_bytes[1],
+            health[0],
+            health[1],
             self.angle.0,
             self.charge,
+            self.conditions.0,
+            disguise,
+            team_class,
+            max_health[0],
+            max_health[1],
         ]
     }
 
-    #[allow(dead_code)]
-    pub fn unpack(bytes: [u8; 8], world: &World) -> Self {
+    pub fn unpack(bytes: [u8; Self::PACKET_SIZE], world: &World) -> Self {
         fn unpack_f32(val: u16, min: f32, max: f32) -> f32 {
             let ratio = val as f32 / (u16::MAX as f32);
             ratio * (max - min) + min
@@ -216,12 +580,15 @@ impl PlayerState {
             world.boundary_min.y,
             world.boundary_max.y,
         );
-        let team_class_health = u16::from_le_bytes([bytes[4], bytes[5]]);
-        let health = team_class_health & 1023;
+        let health = u16::from_le_bytes([bytes[4], bytes[5]]);
         let angle = Angle(bytes[6]);
-        let team = Team::new(team_class_health >> 14);
-        let class = Class::new((team_class_health >> 10) & 15);
         let charge = bytes[7];
+        let conditions = ConditionFlags(bytes[8]);
+        let disguise_team = Team::new((bytes[9] >> 4) as u16);
+        let disguise_class = Class::new((bytes[9] & 15) as u16);
+        let team = Team::new((bytes[10] >> 4) as u16);
+        let class = Class::new((bytes[10] & 15) as u16);
+        let max_health = u16::from_le_bytes([bytes[11], bytes[12]]);
 
         PlayerState {
             position: VectorXY { x, y },
@@ -230,6 +597,10 @@ impl PlayerState {
             team,
             class,
             charge,
+            conditions,
+            disguise_team,
+            disguise_class,
+            max_health,
         }
     }
 }
@@ -257,10 +628,14 @@ fn test_player_packing() {
             y: -5000.0,
         },
         angle: Angle::from(213.0),
-        health: 250,
+        health: 2500,
         team: Team::Blue,
         class: Class::Demoman,
         charge: 7,
+        conditions: ConditionFlags(ConditionFlags::DISGUISED | ConditionFlags::BURNING),
+        disguise_team: Team::Red,
+        disguise_class: Class::Medic,
+        max_health: 3300,
     };
 
     let bytes = input.pack(&world);
@@ -271,11 +646,27 @@ fn test_player_packing() {
     assert_eq!(input.class, unpacked.class);
     assert_eq!(input.team, unpacked.team);
     assert_eq!(input.charge, unpacked.charge);
+    assert_eq!(input.conditions, unpacked.conditions);
+    assert_eq!(input.disguise_team, unpacked.disguise_team);
+    assert_eq!(input.disguise_class, unpacked.disguise_class);
+    assert_eq!(input.max_health, unpacked.max_health);
 
     assert!(f32::abs(input.position.x - unpacked.position.x) < 0.5);
     assert!(f32::abs(input.position.y - unpacked.position.y) < 0.5);
 }
 
+#[test]
+fn test_condition_flags() {
+    // invulnerable, burning and the concheror buff
+    let conditions = [1 << 5, 0, 1 << 6, 1 << 5];
+    let flags = ConditionFlags::new(&conditions);
+    assert!(flags.contains(ConditionFlags::UBERCHARGED));
+    assert!(flags.contains(ConditionFlags::BURNING));
+    assert!(flags.contains(ConditionFlags::BUFFED));
+    assert!(!flags.contains(ConditionFlags::CLOAKED));
+    assert!(!flags.contains(ConditionFlags::KRITZKRIEGED));
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 #[repr(u8)]
 pub enum BuildingType {
@@ -342,22 +733,96 @@ pub struct BuildingState {
     team: Team,
     ty: BuildingType,
     level: u8,
+    status: BuildingStatus,
+    /// Construction or upgrade progress in percent
+    progress: u8,
+}
+
+/// Status flags for a building, as exposed to js
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BuildingStatus(pub u8);
+
+impl BuildingStatus {
+    pub const BUILDING: u8 = 1 << 0;
+    pub const UPGRADING: u8 = 1 << 1;
+    pub const CARRIED: u8 = 1 << 2;
+    pub const SAPPED: u8 = 1 << 3;
+    pub const WRANGLED: u8 = 1 << 4;
+
+    pub fn new(building: &Building) -> Self {
+        let (building_flags, wrangled) = match building {
+            Building::Sentry(sentry) => (
+                [
+                    sentry.building,
+                    sentry.upgrading,
+                    sentry.carried,
+                    sentry.sapped,
+                ],
+                sentry.player_controlled,
+            ),
+            Building::Dispenser(dispenser) => (
+                [
+                    dispenser.building,
+                    dispenser.upgrading,
+                    dispenser.carried,
+                    dispenser.sapped,
+                ],
+                false,
+            ),
+            Building::Teleporter(teleporter) => (
+                [
+                    teleporter.building,
+                    teleporter.upgrading,
+                    teleporter.carried,
+                    teleporter.sapped,
+                ],
+                false,
+            ),
+        };
+
+        let flag_map = [Self::BUILDING, Self::UPGRADING, Self::CARRIED, Self::SAPPED];
+
+        let mut flags = 0;
+        for (set, flag) in building_flags.into_iter().zip(flag_map) {
+            if set {
+                flags |= flag;
+            }
+        }
+        if wrangled {
+            flags |= Self::WRANGLED;
+        }
+        BuildingStatus(flags)
+    }
+}
+
+fn construction_progress(building: &Building) -> u8 {
+    let progress = match building {
+        Building::Sentry(sentry) => sentry.construction_progress,
+        Building::Dispenser(dispenser) => dispenser.construction_progress,
+        Building::Teleporter(teleporter) => teleporter.construction_progress,
+    };
+    (progress.clamp(0.0, 1.0) * 100.0).round() as u8
 }
 
 // for the purpose of viewing the demo in the browser we dont really need high accuracy for
 // position or angle, so we save a bunch of space by truncating those down to half the number
 // of bits
-fn pack_f32(val: f32, min: f32, max: f32) -> u16 {
+pub fn pack_f32(val: f32, min: f32, max: f32) -> u16 {
     let ratio = (val - min) / (max - min);
     (ratio * u16::MAX as f32) as u16
 }
-fn unpack_f32(val: u16, min: f32, max: f32) -> f32 {
+pub fn unpack_f32(val: u16, min: f32, max: f32) -> f32 {
     let ratio = val as f32 / (u16::MAX as f32);
     ratio * (max - min) + min
 }
 
 impl BuildingState {
-    const PACKET_SIZE: usize = 7;
+    /// Version of the packet layout, bumped whenever the layout changes
+    ///
+    /// - 1: 2 bits level, 1 bit team, 3 bits type, 10 bits health, 7 bytes
+    /// - 2: status flags and construction progress appended, 9 bytes
+    pub const PACKET_VERSION: u8 = 2;
+    const PACKET_SIZE: usize = 9;
 
     pub fn new(building: &Building) -> Self {
         let position = building.position();
@@ -371,6 +836,8 @@ impl BuildingState {
             team: building.team(),
             ty: BuildingType::from_building(building),
             level: building.level(),
+            status: BuildingStatus::new(building),
+            progress: construction_progress(building),
         }
     }
 
@@ -380,12 +847,12 @@ impl BuildingState {
         // 2 bits level
         // 1 bit team
         // 3 bits for type
-        // 10 bits for health
+        // 10 bits for health, saturated so it can't overflow into the type
         let team = if self.team == Team::Blue { 0 } else { 1 };
         let team_type_health = ((self.level as u16) << 14)
             + ((team as u16) << 13)
             + ((self.ty as u16) << 10)
-            + self.health;
+            + self.health.min(1023);
         let combined_bytes = team_type_health.to_le_bytes();
 
         [
@@ -396,6 +863,8 @@ impl BuildingState {
             combined_bytes[0],
             combined_bytes[1],
             self.angle.0,
+            self.status.0,
+            self.progress,
         ]
     }
 
@@ -422,6 +891,8 @@ impl BuildingState {
         };
         let ty = BuildingType::new((team_type_health >> 10) as u8 & 7);
         let level = (team_type_health >> 14) as u8;
+        let status = BuildingStatus(bytes[7]);
+        let progress = bytes[8];
 
         BuildingState {
             position: VectorXY { x, y },
@@ -430,6 +901,8 @@ impl BuildingState {
             team,
             ty,
             level,
+            status,
+            progress,
         }
     }
 }
@@ -461,6 +934,8 @@ fn test_building_packing() {
         team: Team::Blue,
         level: 3,
         ty: BuildingType::Level1Sentry,
+        status: BuildingStatus(BuildingStatus::UPGRADING | BuildingStatus::SAPPED),
+        progress: 42,
     };
 
     let bytes = input.pack(&world);
@@ -471,6 +946,17 @@ fn test_building_packing() {
     assert_eq!(input.ty, unpacked.ty);
     assert_eq!(input.team, unpacked.team);
     assert_eq!(input.level, unpacked.level);
+    assert_eq!(input.status, unpacked.status);
+    assert_eq!(input.progress, unpacked.progress);
+
+    let overhealed = BuildingState {
+        health: 2000,
+        ..input.clone()
+    };
+    let unpacked = BuildingState::unpack(overhealed.pack(&world), &world);
+    assert_eq!(1023, unpacked.health);
+    assert_eq!(input.ty, unpacked.ty);
+    assert_eq!(input.team, unpacked.team);
 
     assert!(f32::abs(input.position.x - unpacked.position.x) < 0.5);
     assert!(f32::abs(input.position.y - unpacked.position.y) < 0.5);
@@ -481,13 +967,25 @@ pub struct ProjectileState {
     position: VectorXY,
     team: Team,
     ty: ProjectileType,
+    critical: bool,
     angle: Angle,
+    /// Player slot of the player that fired the projectile, or reflected it last
+    owner: Option<usize>,
 }
 
 impl ProjectileState {
-    const PACKET_SIZE: usize = 6;
+    /// Version of the packet layout, bumped whenever the layout changes
+    ///
+    /// - 1: 1 bit team, 3 bits type, 6 bytes
+    /// - 2: full byte for the type, flags byte with team and crit, 7 bytes
+    /// - 3: owner player slot + 1 (0 if unknown) appended, 8 bytes
+    pub const PACKET_VERSION: u8 = 3;
+    const PACKET_SIZE: usize = 8;
+
+    const FLAG_RED: u8 = 1 << 0;
+    const FLAG_CRIT: u8 = 1 << 1;
 
-    pub fn new(projectile: &Projectile) -> Self {
+    pub fn new(projectile: &Projectile, owner: Option<usize>) -> Self {
         let position = projectile.position;
         ProjectileState {
             position: VectorXY {
@@ -497,19 +995,36 @@ impl ProjectileState {
             angle: Angle::from(projectile.rotation.y),
             team: projectile.team,
             ty: projectile.ty,
+            critical: projectile.critical,
+            owner,
         }
     }
 
     pub fn pack(&self, world: &World) -> [u8; Self::PACKET_SIZE] {
         let x = pack_f32(self.position.x, world.boundary_min.x, world.boundary_max.x).to_le_bytes();
         let y = pack_f32(self.position.y, world.boundary_min.y, world.boundary_max.y).to_le_bytes();
-        // 1 bit team
-        // 3 bits for type
-        // 4 bits for angle, 16 angles should be enough for projectiles
-        let team = if self.team == Team::Blue { 0 } else { 1 };
-        let team_type = ((self.ty as u8) << 5) + ((team as u8) << 4);
+        let mut flags = 0;
+        if self.team != Team::Blue {
+            flags |= Self::FLAG_RED;
+        }
+        if self.critical {
+            flags |= Self::FLAG_CRIT;
+        }
+
+        let owner = self
+            .owner
+            .map_or(0, |slot| u8::try_from(slot + 1).unwrap_or(0));
 
-        [x[0], x[1], y[0], y[1], team_type, self.angle.0]
+        [
+            x[0],
+            x[1],
+            y[0],
+            y[1],
+            self.ty as u8,
+            flags,
+            self.angle.0,
+            owner,
+        ]
     }
 
     #[allow(dead_code)]
@@ -524,21 +1039,24 @@ impl ProjectileState {
             world.boundary_min.y,
             world.boundary_max.y,
         );
-        let team_type = bytes[4];
-        let packed_team = (team_type >> 4) & 1;
-        let team = if packed_team == 0 {
+        let ty = ProjectileType::from(bytes[4]);
+        let flags = bytes[5];
+        let team = if flags & Self::FLAG_RED == 0 {
             Team::Blue
         } else {
             Team::Red
         };
-        let ty = ProjectileType::from((team_type >> 5) & 7);
-        let angle = Angle(bytes[5]);
+        let critical = flags & Self::FLAG_CRIT != 0;
+        let angle = Angle(bytes[6]);
+        let owner = (bytes[7] as usize).checked_sub(1);
 
         ProjectileState {
             position: VectorXY { x, y },
             angle,
             team,
             ty,
+            critical,
+            owner,
         }
     }
 }
@@ -566,8 +1084,10 @@ fn test_projectile_packing() {
             y: -5000.0,
         },
         angle: Angle::from(123.0),
-        team: Team::Blue,
+        team: Team::Red,
         ty: ProjectileType::Flare,
+        critical: true,
+        owner: Some(3),
     };
 
     let bytes = input.pack(&world);
@@ -575,6 +1095,8 @@ fn test_projectile_packing() {
     let unpacked = ProjectileState::unpack(bytes, &world);
     assert_eq!(input.ty, unpacked.ty);
     assert_eq!(input.team, unpacked.team);
+    assert_eq!(input.critical, unpacked.critical);
+    assert_eq!(input.owner, unpacked.owner);
     assert_eq!(input.angle, unpacked.angle);
 
     assert!(f32::abs(input.position.x - unpacked.position.x) < 0.5);
@@ -619,6 +1141,11 @@ pub enum SearchableEvent {
         building_type: RawBuildingType,
         tick: DemoTick,
     },
+    PlayerTeleported {
+        user_id: UserId,
+        builder_id: UserId,
+        tick: DemoTick,
+    },
 }
 
 impl SearchableEvent {
@@ -640,6 +1167,11 @@ impl SearchableEvent {
                 target_id: UserId::from(event.target_id),
                 tick,
             }),
+            GameEvent::PlayerTeleported(event) => Some(SearchableEvent::PlayerTeleported {
+                user_id: UserId::from(event.user_id),
+                builder_id: UserId::from(event.builder_id),
+                tick,
+            }),
             _ => None,
         }
     }
@@ -0,0 +1,283 @@
+use crate::damage::{DamageRecord, HealRecord};
+use crate::identity::{occupancy_at, PlayerIdentity, SlotOccupancy};
+use crate::kill::{KillFlags, KillRecord};
+use crate::state::{ParsedDemo, PlayerState, SearchableEvent};
+use serde::Serialize;
+use tf_demo_parser::demo::data::DemoTick;
+use tf_demo_parser::demo::parser::gamestateanalyser::{Team, UserId, World};
+
+/// Match summary for a single player
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PlayerStats {
+    pub kills: u32,
+    pub deaths: u32,
+    pub assists: u32,
+    pub ubers: u32,
+    pub buildings_destroyed: u32,
+    /// Damage dealt to other players, excluding self damage
+    pub damage_dealt: u32,
+    /// Damage taken from other players, excluding self damage
+    pub damage_taken: u32,
+    pub healing_given: u32,
+    pub healing_received: u32,
+    /// Seconds
+    pub time_alive: f32,
+    /// Seconds played as each class, indexed by the class id
+    pub class_time: [f32; 10],
+}
+
+/// The slot occupancies credited for a kill
+///
+/// Shared between the match stats and the scoreboard so both count kills the same way
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KillCredit {
+    pub attacker: Option<usize>,
+    pub victim: Option<usize>,
+    pub assister: Option<usize>,
+}
+
+impl KillCredit {
+    /// Find the occupancies of the players involved in a kill.
+    ///
+    /// Dead ringer deaths aren't real deaths so they get no credit at all, suicides count as a
+    /// death but not as a kill.
+    pub fn new(kill: &KillRecord, occupancies: &[SlotOccupancy]) -> Option<Self> {
+        if kill.flags.contains(KillFlags::FEIGN_DEATH) {
+            return None;
+        }
+        let find = |user_id: u16| occupancy_at(occupancies, UserId::from(user_id), kill.tick);
+        Some(KillCredit {
+            attacker: (kill.attacker_id != kill.victim_id)
+                .then(|| find(kill.attacker_id))
+                .flatten(),
+            victim: find(kill.victim_id),
+            assister: find(kill.assister_id),
+        })
+    }
+}
+
+impl PlayerStats {
+    /// Aggregate the stats for every slot occupancy in the demo, indexed the same way as the
+    /// occupancies
+    pub fn collect(parsed: &ParsedDemo, world: &World) -> Vec<PlayerStats> {
+        let occupancies = &parsed.occupancies;
+        let mut stats = vec![PlayerStats::default(); occupancies.len()];
+        let interval_per_tick = parsed.header.duration / (parsed.header.ticks as f32);
+
+        count_kills(&mut stats, &parsed.kills, occupancies);
+        count_damage(&mut stats, &parsed.damage, occupancies);
+        count_healing(&mut stats, &parsed.healing, occupancies);
+
+        for event in parsed.events.iter() {
+            match event {
+                SearchableEvent::Uber { user_id, tick, .. } => {
+                    if let Some(medic) = stats_for_user(&mut stats, occupancies, *user_id, *tick) {
+                        medic.ubers += 1;
+                    }
+                }
+                SearchableEvent::BuildingDestroyed {
+                    attacker_id, tick, ..
+                } => {
+                    if let Some(attacker) =
+                        stats_for_user(&mut stats, occupancies, *attacker_id, *tick)
+                    {
+                        attacker.buildings_destroyed += 1;
+                    }
+                }
+                SearchableEvent::PlayerTeleported { .. } => {}
+            }
+        }
+
+        for (slot, packets) in parsed.players.iter().enumerate() {
+            // (connect tick, occupancy index) for everyone that used this slot
+            let slot_occupancies: Vec<(u32, usize)> = occupancies
+                .iter()
+                .enumerate()
+                .filter(|(_, occupancy)| occupancy.slot == slot)
+                .map(|(index, occupancy)| (u32::from(occupancy.connect_tick), index))
+                .collect();
+            let mut current = slot_occupancies.first().map(|(_, index)| *index);
+            let mut next = 0;
+
+            for (tick, packet) in packets.chunks_exact(PlayerState::PACKET_SIZE).enumerate() {
+                let tick = tick as u32;
+                while let Some((connect_tick, index)) = slot_occupancies.get(next) {
+                    if *connect_tick > tick {
+                        break;
+                    }
+                    current = Some(*index);
+                    next += 1;
+                }
+                let Some(player_stats) = current.and_then(|index| stats.get_mut(index)) else {
+                    continue;
+                };
+                let Ok(bytes) = packet.try_into() else {
+                    continue;
+                };
+                let player = PlayerState::unpack(bytes, world);
+                if !matches!(player.team, Team::Red | Team::Blue) {
+                    continue;
+                }
+                if player.health > 0 {
+                    player_stats.time_alive += interval_per_tick;
+                }
+                if let Some(class_time) = player_stats.class_time.get_mut(player.class as usize) {
+                    *class_time += interval_per_tick;
+                }
+            }
+        }
+
+        stats
+    }
+
+    /// Total the occupancy stats for every player slot
+    pub fn per_slot(
+        stats: &[PlayerStats],
+        occupancies: &[SlotOccupancy],
+        slot_count: usize,
+    ) -> Vec<PlayerStats> {
+        let mut totals = vec![PlayerStats::default(); slot_count];
+        for (occupancy_stats, occupancy) in stats.iter().zip(occupancies) {
+            if let Some(total) = totals.get_mut(occupancy.slot) {
+                total.add(occupancy_stats);
+            }
+        }
+        totals
+    }
+
+    /// Total the occupancy stats for every identity, indexed the same way as
+    /// `PlayerIdentity::group`
+    pub fn per_identity(stats: &[PlayerStats], occupancies: &[SlotOccupancy]) -> Vec<PlayerStats> {
+        let mut totals: Vec<PlayerStats> = Vec::new();
+        for (occupancy_stats, identity) in stats.iter().zip(PlayerIdentity::indexes(occupancies)) {
+            if totals.len() <= identity {
+                totals.resize(identity + 1, PlayerStats::default());
+            }
+            totals[identity].add(occupancy_stats);
+        }
+        totals
+    }
+
+    fn add(&mut self, other: &PlayerStats) {
+        self.kills += other.kills;
+        self.deaths += other.deaths;
+        self.assists += other.assists;
+        self.ubers += other.ubers;
+        self.buildings_destroyed += other.buildings_destroyed;
+        self.damage_dealt += other.damage_dealt;
+        self.damage_taken += other.damage_taken;
+        self.healing_given += other.healing_given;
+        self.healing_received += other.healing_received;
+        self.time_alive += other.time_alive;
+        for (total, time) in self.class_time.iter_mut().zip(other.class_time) {
+            *total += time;
+        }
+    }
+}
+
+fn count_kills(stats: &mut [PlayerStats], kills: &[KillRecord], occupancies: &[SlotOccupancy]) {
+    for credit in kills
+        .iter()
+        .filter_map(|kill| KillCredit::new(kill, occupancies))
+    {
+        if let Some(victim) = credit.victim.and_then(|index| stats.get_mut(index)) {
+            victim.deaths += 1;
+        }
+        if let Some(attacker) = credit.attacker.and_then(|index| stats.get_mut(index)) {
+            attacker.kills += 1;
+        }
+        if let Some(assister) = credit.assister.and_then(|index| stats.get_mut(index)) {
+            assister.assists += 1;
+        }
+    }
+}
+
+fn count_damage(stats: &mut [PlayerStats], damage: &[DamageRecord], occupancies: &[SlotOccupancy]) {
+    for damage in damage.iter().filter(|damage| !damage.is_self_damage()) {
+        let attacker_id = UserId::from(damage.attacker_id);
+        if let Some(attacker) = stats_for_user(stats, occupancies, attacker_id, damage.tick) {
+            attacker.damage_dealt += damage.damage as u32;
+        }
+        let victim_id = UserId::from(damage.victim_id);
+        if let Some(victim) = stats_for_user(stats, occupancies, victim_id, damage.tick) {
+            victim.damage_taken += damage.damage as u32;
+        }
+    }
+}
+
+fn count_healing(stats: &mut [PlayerStats], healing: &[HealRecord], occupancies: &[SlotOccupancy]) {
+    for heal in healing.iter() {
+        let healer_id = UserId::from(heal.healer_id);
+        if let Some(healer) = stats_for_user(stats, occupancies, healer_id, heal.tick) {
+            healer.healing_given += heal.amount as u32;
+        }
+        let patient_id = UserId::from(heal.patient_id);
+        if let Some(patient) = stats_for_user(stats, occupancies, patient_id, heal.tick) {
+            patient.healing_received += heal.amount as u32;
+        }
+    }
+}
+
+fn stats_for_user<'a>(
+    stats: &'a mut [PlayerStats],
+    occupancies: &[SlotOccupancy],
+    user_id: UserId,
+    tick: DemoTick,
+) -> Option<&'a mut PlayerStats> {
+    occupancy_at(occupancies, user_id, tick).and_then(|index| stats.get_mut(index))
+}
+
+#[test]
+fn test_kill_and_damage_exclusions() {
+    use crate::kill::CritType;
+
+    let occupancy = |slot: usize, user_id: u16| SlotOccupancy {
+        slot,
+        user_id: UserId::from(user_id),
+        steam_id: format!("[U:1:{}]", user_id),
+        name: String::new(),
+        connect_tick: DemoTick::default(),
+        disconnect_tick: None,
+    };
+    let occupancies = [occupancy(0, 2), occupancy(1, 3), occupancy(2, 4)];
+
+    let kill = |attacker_id: u16, victim_id: u16, assister_id: u16, flags: u16| KillRecord {
+        attacker_id,
+        victim_id,
+        assister_id,
+        flags: KillFlags(flags),
+        ..KillRecord::default()
+    };
+    let kills = [
+        kill(2, 3, 4, 0),
+        // dead ringer
+        kill(2, 3, 0, KillFlags::FEIGN_DEATH),
+        // suicide
+        kill(3, 3, 0, 0),
+    ];
+
+    let damage = |attacker_id: u16, victim_id: u16, damage: u16| DamageRecord {
+        tick: DemoTick::default(),
+        attacker_id,
+        victim_id,
+        damage,
+        victim_health: 0,
+        weapon_id: 0,
+        crit_type: CritType::None,
+    };
+    let damages = [damage(2, 3, 100), damage(2, 2, 50)];
+
+    let mut stats = vec![PlayerStats::default(); occupancies.len()];
+    count_kills(&mut stats, &kills, &occupancies);
+    count_damage(&mut stats, &damages, &occupancies);
+
+    assert_eq!(1, stats[0].kills);
+    assert_eq!(0, stats[0].deaths);
+    assert_eq!(0, stats[1].kills);
+    assert_eq!(2, stats[1].deaths);
+    assert_eq!(1, stats[2].assists);
+
+    assert_eq!(100, stats[0].damage_dealt);
+    assert_eq!(0, stats[0].damage_taken);
+    assert_eq!(100, stats[1].damage_taken);
+}
@@ -0,0 +1,155 @@
+use crate::state::projectile_owner;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tf_demo_parser::demo::data::game_state::ProjectileType;
+use tf_demo_parser::demo::data::DemoTick;
+use tf_demo_parser::demo::message::packetentities::EntityId;
+use tf_demo_parser::demo::parser::gamestateanalyser::{Class, GameState, PlayerState, Team};
+use tf_demo_parser::demo::vector::Vector;
+
+/// Stickies that move less than this many units between updates are considered placed
+const PLACED_THRESHOLD: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StickyEnd {
+    /// Removed while the owner was still alive and playing the same class
+    Detonated,
+    /// Removed because the owner died or switched class
+    Destroyed,
+    /// The demo ended before the sticky was removed
+    Unfinished,
+}
+
+/// A sticky that came to rest and stayed around as a trap
+#[derive(Debug, Clone, Serialize)]
+pub struct StickyTrap {
+    /// Player slot of the demoman that fired the sticky, if it could be found
+    pub owner: Option<usize>,
+    pub team: Team,
+    pub placed_tick: DemoTick,
+    pub position: Vector,
+    pub end_tick: Option<DemoTick>,
+    pub end: StickyEnd,
+}
+
+#[derive(Debug)]
+struct ActiveSticky {
+    last_position: Vector,
+    /// Index into the list of traps once the sticky has been placed
+    trap: Option<usize>,
+    /// Class of the owner when the sticky was placed, to detect class switches
+    owner_class: Option<Class>,
+}
+
+/// Follow the stickies in the projectile list to find the sticky traps.
+///
+/// We can't see whether a sticky is stuck to a surface, so a sticky is considered placed once it
+/// stops moving. Stickies that get shot by the enemy are counted as detonated since the removal
+/// looks the same.
+#[derive(Debug, Default)]
+pub struct StickyTracker {
+    active: BTreeMap<EntityId, ActiveSticky>,
+    pub traps: Vec<StickyTrap>,
+}
+
+impl StickyTracker {
+    pub fn update(&mut self, game_state: &GameState) {
+        let stickies = game_state
+            .projectiles
+            .iter()
+            .filter(|(_, projectile)| projectile.ty == ProjectileType::Sticky);
+
+        for (id, projectile) in stickies {
+            let Some(active) = self.active.get_mut(id) else {
+                self.active.insert(
+                    *id,
+                    ActiveSticky {
+                        last_position: projectile.position,
+                        trap: None,
+                        owner_class: None,
+                    },
+                );
+                continue;
+            };
+
+            if active.trap.is_none() && !moved(active.last_position, projectile.position) {
+                let owner = projectile_owner(game_state, projectile);
+                active.trap = Some(self.traps.len());
+                active.owner_class = owner
+                    .and_then(|slot| game_state.players.get(slot))
+                    .map(|player| player.class);
+                self.traps.push(StickyTrap {
+                    owner,
+                    team: projectile.team,
+                    placed_tick: game_state.tick,
+                    position: projectile.position,
+                    end_tick: None,
+                    end: StickyEnd::Unfinished,
+                });
+            }
+            active.last_position = projectile.position;
+        }
+
+        let traps = &mut self.traps;
+        self.active.retain(|id, active| {
+            if game_state
+                .projectiles
+                .get(id)
+                .is_some_and(|projectile| projectile.ty == ProjectileType::Sticky)
+            {
+                return true;
+            }
+            if let Some(trap) = active.trap.and_then(|index| traps.get_mut(index)) {
+                let detonated = trap
+                    .owner
+                    .and_then(|slot| game_state.players.get(slot))
+                    .is_some_and(|player| {
+                        player.state == PlayerState::Alive
+                            && Some(player.class) == active.owner_class
+                    });
+                trap.end_tick = Some(game_state.tick);
+                trap.end = if detonated {
+                    StickyEnd::Detonated
+                } else {
+                    StickyEnd::Destroyed
+                };
+            }
+            false
+        });
+    }
+}
+
+fn moved(from: Vector, to: Vector) -> bool {
+    (from.x - to.x).abs() > PLACED_THRESHOLD
+        || (from.y - to.y).abs() > PLACED_THRESHOLD
+        || (from.z - to.z).abs() > PLACED_THRESHOLD
+}
+
+/// Get the sticky traps that are placed at a tick
+pub fn traps_at(traps: &[StickyTrap], tick: DemoTick) -> Vec<&StickyTrap> {
+    traps
+        .iter()
+        .filter(|trap| trap.placed_tick <= tick && trap.end_tick.is_none_or(|end| tick < end))
+        .collect()
+}
+
+#[test]
+fn test_traps_at() {
+    let trap = |placed: u32, end: Option<u32>| StickyTrap {
+        owner: Some(1),
+        team: Team::Blue,
+        placed_tick: DemoTick::from(placed),
+        position: Vector::default(),
+        end_tick: end.map(DemoTick::from),
+        end: StickyEnd::Detonated,
+    };
+    let traps = [trap(10, Some(20)), trap(15, None), trap(30, Some(40))];
+
+    let placed = |tick: u32| traps_at(&traps, DemoTick::from(tick)).len();
+    assert_eq!(0, placed(5));
+    assert_eq!(2, placed(15));
+    assert_eq!(1, placed(20));
+    assert_eq!(2, placed(35));
+    assert_eq!(1, placed(40));
+}
@@ -0,0 +1,161 @@
+use crate::columns::BuildingColumns;
+use crate::history::{entry_at, Timed};
+use serde::Serialize;
+use tf_demo_parser::demo::data::DemoTick;
+use tf_demo_parser::demo::message::packetentities::EntityId;
+use tf_demo_parser::demo::parser::gamestateanalyser::{Building, GameState, Team, UserId};
+
+/// The exit a teleporter entrance was linked to starting at a specific tick
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TeleporterLink {
+    pub tick: DemoTick,
+    /// Whether the building column holds a teleporter entrance
+    pub entrance: bool,
+    /// Building column of the exit, `None` if the builder has no exit
+    pub exit: Option<usize>,
+}
+
+impl Timed for TeleporterLink {
+    fn tick(&self) -> DemoTick {
+        self.tick
+    }
+}
+
+/// The parts of a teleporter needed to link entrances to exits
+#[derive(Debug, Clone, Copy)]
+struct TeleporterEnd {
+    id: EntityId,
+    entrance: bool,
+    builder: UserId,
+    team: Team,
+}
+
+/// Link teleporter entrances to the exit from the same builder, indexed by building column.
+///
+/// A column that stops holding an entrance gets an unlinked record, so the link of a removed
+/// entrance doesn't carry on into the next building that gets the column.
+#[derive(Debug, Default)]
+pub struct TeleporterTracker {
+    pub links: Vec<Vec<TeleporterLink>>,
+}
+
+impl TeleporterTracker {
+    pub fn update(&mut self, game_state: &GameState, columns: &BuildingColumns) {
+        let teleporters: Vec<TeleporterEnd> = game_state
+            .buildings
+            .iter()
+            .filter_map(|(id, building)| match building {
+                Building::Teleporter(teleporter) => Some(TeleporterEnd {
+                    id: *id,
+                    entrance: teleporter.is_entrance,
+                    builder: teleporter.builder,
+                    team: teleporter.team,
+                }),
+                _ => None,
+            })
+            .collect();
+        self.link(game_state.tick, columns, &teleporters);
+    }
+
+    fn link(&mut self, tick: DemoTick, columns: &BuildingColumns, teleporters: &[TeleporterEnd]) {
+        for index in 0..columns.column_count() {
+            let entrance = columns.building(index).and_then(|id| {
+                teleporters
+                    .iter()
+                    .find(|teleporter| teleporter.id == id && teleporter.entrance)
+            });
+            let exit = entrance.map(|entrance| {
+                teleporters
+                    .iter()
+                    .find(|exit| {
+                        !exit.entrance
+                            && exit.builder == entrance.builder
+                            && exit.team == entrance.team
+                    })
+                    .and_then(|exit| columns.column(exit.id))
+            });
+            self.record(index, tick, exit);
+        }
+    }
+
+    /// Record the link for a column, `None` if the column doesn't hold an entrance
+    fn record(&mut self, index: usize, tick: DemoTick, exit: Option<Option<usize>>) {
+        if self.links.len() <= index {
+            if exit.is_none() {
+                return;
+            }
+            self.links.resize_with(index + 1, Vec::new);
+        }
+
+        let link = TeleporterLink {
+            tick,
+            entrance: exit.is_some(),
+            exit: exit.flatten(),
+        };
+        let history = &mut self.links[index];
+        if history
+            .last()
+            .is_none_or(|last| last.entrance != link.entrance || last.exit != link.exit)
+        {
+            history.push(link);
+        }
+    }
+}
+
+/// Get the exit a teleporter entrance was linked to at a specific tick
+pub fn exit_at(history: &[TeleporterLink], tick: DemoTick) -> Option<usize> {
+    entry_at(history, tick).and_then(|link| link.exit)
+}
+
+#[test]
+fn test_teleporter_link_columns() {
+    let teleporter = |id: u32, entrance: bool| TeleporterEnd {
+        id: EntityId::from(id),
+        entrance,
+        builder: UserId::from(5u16),
+        team: Team::Blue,
+    };
+    let teleporters = [teleporter(20, true), teleporter(30, false)];
+    let mut columns = BuildingColumns::default();
+    let mut tracker = TeleporterTracker::default();
+
+    // a dispenser, the entrance and the exit
+    columns.update(&[
+        EntityId::from(10u32),
+        EntityId::from(20u32),
+        EntityId::from(30u32),
+    ]);
+    tracker.link(DemoTick::from(10u32), &columns, &teleporters);
+    // the dispenser gets destroyed, the teleporters keep their columns
+    columns.update(&[EntityId::from(20u32), EntityId::from(30u32)]);
+    tracker.link(DemoTick::from(20u32), &columns, &teleporters);
+
+    assert!(tracker.links[0].is_empty());
+    assert_eq!(1, tracker.links[1].len());
+    assert_eq!(Some(2), exit_at(&tracker.links[1], DemoTick::from(25u32)));
+}
+
+#[test]
+fn test_teleporter_column_reuse() {
+    let mut tracker = TeleporterTracker::default();
+    // an exit in the column doesn't start a history
+    tracker.record(2, DemoTick::from(5u32), None);
+    assert!(tracker.links.is_empty());
+
+    // entrance without an exit, then the exit gets built in column 1
+    tracker.record(0, DemoTick::from(10u32), Some(None));
+    tracker.record(0, DemoTick::from(20u32), Some(Some(1)));
+    tracker.record(0, DemoTick::from(21u32), Some(Some(1)));
+    // the entrance gets destroyed and a sentry is built in the same column
+    tracker.record(0, DemoTick::from(30u32), None);
+    tracker.record(0, DemoTick::from(40u32), None);
+
+    let history = &tracker.links[0];
+    assert_eq!(3, history.len());
+    assert!(!history[2].entrance);
+    assert_eq!(None, exit_at(history, DemoTick::from(5u32)));
+    assert_eq!(None, exit_at(history, DemoTick::from(15u32)));
+    assert_eq!(Some(1), exit_at(history, DemoTick::from(25u32)));
+    assert_eq!(None, exit_at(history, DemoTick::from(30u32)));
+    assert_eq!(None, exit_at(history, DemoTick::from(45u32)));
+}
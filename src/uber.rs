@@ -0,0 +1,228 @@
+use crate::identity::slot_for_user;
+use crate::state::{ParsedDemo, PlayerState, SearchableEvent};
+use serde::Serialize;
+use tf_demo_parser::demo::parser::gamestateanalyser::{Class, UserId, World};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChargeEnd {
+    Deployed,
+    /// The medic died or switched class with a full charge
+    Dropped,
+    /// The medic died or switched class before the charge was full
+    Lost,
+    /// The demo ended before the charge was used
+    Unfinished,
+}
+
+/// A single medic charge, from the first percent build to it being used or lost
+#[derive(Debug, Clone, Serialize)]
+pub struct ChargeCycle {
+    pub start_tick: u32,
+    /// The tick the charge reached 100%
+    pub full_tick: Option<u32>,
+    pub end_tick: u32,
+    /// Number of ticks the charge was held at 100% before being used or lost
+    pub held_ticks: u32,
+    pub end: ChargeEnd,
+    /// The player targeted when the charge was deployed
+    pub target_id: Option<UserId>,
+}
+
+impl ChargeCycle {
+    fn new(start_tick: u32) -> Self {
+        ChargeCycle {
+            start_tick,
+            full_tick: None,
+            end_tick: start_tick,
+            held_ticks: 0,
+            end: ChargeEnd::Unfinished,
+            target_id: None,
+        }
+    }
+
+    fn close(mut self, tick: u32, end: ChargeEnd) -> Self {
+        self.end_tick = tick;
+        self.end = end;
+        self.held_ticks = self.full_tick.map_or(0, |full| tick.saturating_sub(full));
+        self
+    }
+
+    /// Derive the charge cycles for every player slot from the packed charge levels and the
+    /// uber deploy events
+    pub fn collect(parsed: &ParsedDemo, world: &World) -> Vec<Vec<ChargeCycle>> {
+        parsed
+            .players
+            .iter()
+            .enumerate()
+            .map(|(slot, packets)| {
+                let deploys: Vec<(u32, UserId)> = parsed
+                    .events
+                    .iter()
+                    .filter_map(|event| match event {
+                        SearchableEvent::Uber {
+                            user_id,
+                            target_id,
+                            tick,
+                        } if slot_for_user(&parsed.occupancies, *user_id) == Some(slot) => {
+                            Some((u32::from(*tick), *target_id))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                Self::collect_for_player(packets, &deploys, world)
+            })
+            .collect()
+    }
+
+    fn collect_for_player(
+        packets: &[u8],
+        deploys: &[(u32, UserId)],
+        world: &World,
+    ) -> Vec<ChargeCycle> {
+        let mut cycles = Vec::new();
+        let mut current: Option<ChargeCycle> = None;
+        let mut deploys = deploys.iter().peekable();
+        // after deploying, the charge drains back to 0 before a new charge can be built
+        let mut draining = false;
+
+        for (tick, packet) in packets.chunks_exact(PlayerState::PACKET_SIZE).enumerate() {
+            let Ok(bytes) = packet.try_into() else {
+                continue;
+            };
+            let player = PlayerState::unpack(bytes, world);
+            let tick = tick as u32;
+
+            if let Some((_, target_id)) = deploys.next_if(|(deploy_tick, _)| *deploy_tick <= tick) {
+                let cycle = current.take().unwrap_or_else(|| ChargeCycle::new(tick));
+                let mut cycle = cycle.close(tick, ChargeEnd::Deployed);
+                cycle.target_id = Some(*target_id);
+                cycles.push(cycle);
+                draining = true;
+                continue;
+            }
+
+            if player.class != Class::Medic || player.health == 0 {
+                if let Some(cycle) = current.take() {
+                    let end = if cycle.full_tick.is_some() {
+                        ChargeEnd::Dropped
+                    } else {
+                        ChargeEnd::Lost
+                    };
+                    cycles.push(cycle.close(tick, end));
+                }
+                draining = false;
+                continue;
+            }
+
+            if draining {
+                draining = player.charge > 0;
+                continue;
+            }
+
+            if player.charge > 0 {
+                let cycle = current.get_or_insert_with(|| ChargeCycle::new(tick));
+                if player.charge >= 100 && cycle.full_tick.is_none() {
+                    cycle.full_tick = Some(tick);
+                }
+            }
+        }
+
+        if let Some(cycle) = current {
+            let end_tick = (packets.len() / PlayerState::PACKET_SIZE) as u32;
+            cycles.push(cycle.close(end_tick, ChargeEnd::Unfinished));
+        }
+
+        cycles
+    }
+}
+
+#[test]
+fn test_charge_cycles() {
+    use tf_demo_parser::demo::parser::gamestateanalyser::Team;
+    use tf_demo_parser::demo::vector::{Vector, VectorXY};
+
+    let world = World {
+        boundary_max: Vector {
+            x: 10000.0,
+            y: 10000.0,
+            z: 100.0,
+        },
+        boundary_min: Vector {
+            x: -10000.0,
+            y: -10000.0,
+            z: -100.0,
+        },
+    };
+
+    let medic = |health: u16, charge: u8| PlayerState {
+        position: VectorXY { x: 0.0, y: 0.0 },
+        angle: Default::default(),
+        health,
+        team: Team::Blue,
+        class: Class::Medic,
+        charge,
+        conditions: Default::default(),
+        disguise_team: Team::Other,
+        disguise_class: Class::Other,
+        max_health: 150,
+    };
+    let soldier = PlayerState {
+        class: Class::Soldier,
+        max_health: 200,
+        ..medic(200, 0)
+    };
+
+    let states = [
+        medic(150, 0),
+        medic(150, 50),
+        medic(150, 100),
+        medic(150, 100),
+        // deploy
+        medic(150, 90),
+        medic(150, 10),
+        medic(150, 0),
+        medic(150, 20),
+        medic(150, 100),
+        // drop
+        medic(0, 100),
+        medic(150, 0),
+        medic(150, 30),
+        medic(150, 100),
+        // switched class with a full charge
+        soldier,
+        medic(150, 40),
+        // switched class before the charge was full
+        soldier,
+        medic(150, 10),
+    ];
+    let packets: Vec<u8> = states.iter().flat_map(|state| state.pack(&world)).collect();
+
+    let cycles = ChargeCycle::collect_for_player(&packets, &[(4, UserId::from(3u16))], &world);
+    assert_eq!(5, cycles.len());
+
+    assert_eq!(1, cycles[0].start_tick);
+    assert_eq!(Some(2), cycles[0].full_tick);
+    assert_eq!(4, cycles[0].end_tick);
+    assert_eq!(2, cycles[0].held_ticks);
+    assert_eq!(ChargeEnd::Deployed, cycles[0].end);
+    assert_eq!(Some(UserId::from(3u16)), cycles[0].target_id);
+
+    assert_eq!(7, cycles[1].start_tick);
+    assert_eq!(Some(8), cycles[1].full_tick);
+    assert_eq!(9, cycles[1].end_tick);
+    assert_eq!(ChargeEnd::Dropped, cycles[1].end);
+
+    assert_eq!(11, cycles[2].start_tick);
+    assert_eq!(Some(12), cycles[2].full_tick);
+    assert_eq!(13, cycles[2].end_tick);
+    assert_eq!(ChargeEnd::Dropped, cycles[2].end);
+
+    assert_eq!(14, cycles[3].start_tick);
+    assert_eq!(None, cycles[3].full_tick);
+    assert_eq!(ChargeEnd::Lost, cycles[3].end);
+
+    assert_eq!(16, cycles[4].start_tick);
+    assert_eq!(None, cycles[4].full_tick);
+    assert_eq!(ChargeEnd::Unfinished, cycles[4].end);
+}